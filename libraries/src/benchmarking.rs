@@ -4,8 +4,10 @@ use common_ciphers::CipherName::*;
 use rand::{rngs::StdRng, RngCore, SeedableRng};
 use rayon::{iter::ParallelIterator, slice::ParallelSliceMut, ThreadPoolBuilder};
 use std::time::Duration;
+use std::io::Write;
 
-use crate::{cipher::{cbc_core::CBCCipherCore, core::SSCipherCore, ctr_core::CTRCipherCore}, common_ciphers::{self, CIPHER_128_NAMES, CIPHER_256_NAMES}, GF128};
+use crate::{cipher::{aead::{compute_tag, PolyMac}, kdf::{cmac, derive_keys, passphrase_kdf, PassphraseParams, MIN_SALT_SIZE}, cbc_core::CBCCipherCore, container::RsbeHeader, stream::{decrypt_stream, encrypt_stream, StreamEncryptor, MAX_SEGMENT_SIZE}, plan::{level_plan, level_plan_with}, core::{fit_thresholds, generate_key_scheme, DecodeSolver, EncodeKernel, LevelParams, MaskSchedule, Rsbe, SSCipherCore, INTERLEAVED_VECTORS, MAX_DEPTH, MIN_THRESHOLD}, ctr_core::CTRCipherCore}, common_ciphers::{self, aes::AES128, aes256::AES256, aes_bitsliced::{AES128Bitsliced, AES256Bitsliced}, aes_ni::AesBackend, CipherInterface, CIPHER_128_NAMES, CIPHER_256_NAMES}, 
+            galois_arithmetic::{backend::Backend, poly::KARATSUBA_THRESHOLD, vector::GF128Vector, GF128Matrix, GF128Poly, VandermondeInverses, VandermondeSolver}, GF128, RsbeError};
 
 pub enum TestParam { KEY,IV }
 
@@ -21,17 +23,139 @@ pub fn threshold_bench(){
     for len in (10240..160000).step_by(1024){
     count+=1;
     for t in 4..30{
-                st = SSCipherCore::new(&data,len, true, &mut out,t,AES128);
-                st.set_key_scheme(&[key], &iv);
+                st = SSCipherCore::with_key(&data,len, true, &mut out,t,AES128,&[key], &iv).unwrap();
                 let start: Instant = Instant::now();
-                st.encrypt();
+                st.encrypt().unwrap();
                 let duration = start.elapsed();
                 results_by_th[t]+=((len*1000000) as f32)/((duration.as_micros() as f32)*((1024*1024*1024) as f32));
                 }
     }
     for t in 2..30{ results_by_th[t]/=count as f32;
     println!("t = {}: {:.3} GB/s",t,results_by_th[t]);
-}}
+}
+    encode_kernel_bench();
+    decode_solver_bench();
+}
+
+// Encryption throughput of the single vector kernel against the interleaved one (best of 5 runs on 8 MiB), both must
+// give the same ciphertext, on the sequential and parallel paths
+pub fn encode_kernel_bench(){
+    println!("{}", "-".repeat(100));
+    let key = GF128::random();
+    let iv = GF128::random();
+    let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let mut data = vec![0u8; 8 << 20];
+    rand::rng().fill_bytes(&mut data);
+    println!("Interleaved kernel : {} vectors per pass", INTERLEAVED_VECTORS);
+    for t in [4usize, 8, 16, 30, 64] {
+        let mut ciphertexts = Vec::new();
+        let mut throughputs = Vec::new();
+        for parallel in [false, true] {
+            for kernel in [EncodeKernel::Single, EncodeKernel::Interleaved] {
+                let mut best = Duration::MAX;
+                for run in 0..5 {
+                    let mut out = Vec::<GF128>::new();
+                    let mut st = SSCipherCore::with_key(&data, data.len(), true, &mut out, t, AES128, &[key], &iv).unwrap();
+                    st.set_mask_schedule(MaskSchedule::Generator);
+                    st.set_encode_kernel(kernel);
+                    let start = Instant::now();
+                    if parallel {st.par_encrypt(&pool).unwrap()} else {st.encrypt().unwrap()}
+                    best = best.min(start.elapsed());
+                    if run == 0 {ciphertexts.push(st.get_bytes_out().to_vec())}
+                }
+                throughputs.push(data.len() as f64 / (1024.0f64 * 1024.0 * 1024.0) / best.as_secs_f64());
+            }
+        }
+        let check = ciphertexts.windows(2).all(|pair| pair[0] == pair[1]);
+        println!("t = {:>2} : single {:.3} GB/s, interleaved {:.3} GB/s (x{:.2}) | 4 threads : single {:.3} GB/s, interleaved {:.3} GB/s (x{:.2}), Check result correctness :{}",
+                 t, throughputs[0], throughputs[1], throughputs[1] / throughputs[0], throughputs[2], throughputs[3], throughputs[3] / throughputs[2], check);
+    }
+}
+
+// Structured Vandermonde solver against the inverse matrices, for t from 4 to 128 : solutions of random systems, cost of
+// the setup (sizes t - 1 and t) and of one vector, then decryption time of a small and a large message with each
+// solver, from a fresh key scheme (best of 3), which must give back the plaintext
+pub fn decode_solver_bench(){
+    println!("{}", "-".repeat(100));
+    let key = GF128::random();
+    let iv = GF128::random();
+    let mut rng = StdRng::seed_from_u64(25);
+    let check = VandermondeSolver::new(&[GF128::from(3), GF128::from(7), GF128::from(3)]).is_err();
+    println!("Duplicate nodes rejected, Check result correctness :{}", check);
+    for t in [4usize, 8, 16, 32, 64, 128] {
+        let matrix = GF128Matrix::vandermonde(&GF128Vector::random(t));
+        let start = Instant::now();
+        let mut inverses = VandermondeInverses::default();
+        inverses.require(&matrix, &[t - 1, t]).unwrap();
+        let inverses_setup = start.elapsed();
+        let start = Instant::now();
+        let solvers = [VandermondeSolver::from_vandermonde(&matrix, t - 1).unwrap(), VandermondeSolver::from_vandermonde(&matrix, t).unwrap()];
+        let solvers_setup = start.elapsed();
+        let mut check = true;
+        for (solver, size) in solvers.iter().zip([t - 1, t]) {
+            let inverse = inverses.get(size).unwrap();
+            for _ in 0..10 {
+                let values: Vec<GF128> = (0..size).map(|_| GF128::from(rng.random::<u128>())).collect();
+                let expected: Vec<GF128> = inverse.chunks(size).map(|row| row.iter().zip(&values).fold(GF128::from(0), |acc, (m, v)| acc + *m * *v)).collect();
+                let (mut solution, mut scratch) = (values.clone(), vec![GF128::from(0); size]);
+                solver.solve(&mut solution, &mut scratch);
+                check &= solution == expected;
+            }
+        }
+        let vectors = 200_000 / (t * t) + 1;
+        let values: Vec<GF128> = (0..t).map(|_| GF128::random()).collect();
+        let inverse = inverses.get(t).unwrap();
+        let start = Instant::now();
+        for _ in 0..vectors {std::hint::black_box(inverse.chunks(t).map(|row| Backend::detect().dot(row, &values)).collect::<Vec<GF128>>());}
+        let inverse_vector = start.elapsed() / vectors as u32;
+        let (mut solution, mut scratch) = (values.clone(), vec![GF128::from(0); t]);
+        let start = Instant::now();
+        for _ in 0..vectors {   solution.copy_from_slice(&values);
+                                solvers[1].solve(&mut solution, &mut scratch);
+                                std::hint::black_box(&solution);
+                            }
+        let solver_vector = start.elapsed() / vectors as u32;
+        println!("t = {:>3} : setup inverses {:?}, solvers {:?} | per vector inverses {:?}, solver {:?}, Check result correctness :{}",
+                 t, inverses_setup, solvers_setup, inverse_vector, solver_vector, check);
+        for size in [16usize << 10, 4 << 20] {
+            let mut data = vec![0u8; size];
+            rand::rng().fill_bytes(&mut data);
+            let mut times = Vec::new();
+            let mut check = true;
+            for solver in [DecodeSolver::Inverses, DecodeSolver::Structured, DecodeSolver::Auto] {
+                let mut best = Duration::MAX;
+                for _ in 0..3 {
+                    let mut out = Vec::<GF128>::new();
+                    let mut st = SSCipherCore::with_key(&data, data.len(), true, &mut out, t, AES128, &[key], &iv).unwrap();
+                    st.encrypt().unwrap();
+                    st.set_decode_solver(solver);
+                    let start = Instant::now();
+                    st.decrypt().unwrap();
+                    best = best.min(start.elapsed());
+                    check &= st.get_bytes_out() == &data[..];
+                }
+                times.push(best);
+            }
+            println!("          decryption of {:>4} KiB : inverses {:?}, structured {:?}, auto {:?}, Check result correctness :{}",
+                     size >> 10, times[0], times[1], times[2], check);
+        }
+    }
+    let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let mut data = vec![0u8; 1 << 20];
+    rand::rng().fill_bytes(&mut data);
+    let mut check = true;
+    for t in [3usize, 40] {
+        for solver in [DecodeSolver::Inverses, DecodeSolver::Structured] {
+            let mut out = Vec::<GF128>::new();
+            let mut st = SSCipherCore::with_key(&data, data.len(), true, &mut out, t, AES128, &[key], &iv).unwrap();
+            st.set_decode_solver(solver);
+            st.par_encrypt(&pool).unwrap();
+            st.par_decrypt(&pool).unwrap();
+            check &= st.get_bytes_out() == &data[..];
+        }
+    }
+    println!("Parallel decryption with both solvers, Check result correctness :{}", check);
+}
 
 pub fn time_benchmark(){
         let mut data = Vec::<u8>::new();    
@@ -59,9 +183,9 @@ pub fn time_benchmark(){
     for c in CIPHER_128_NAMES {
         let t_size :usize =10240;
         let mut out = Vec::<GF128>::new();
-        let mut st = SSCipherCore::new(&data,t_size, true, &mut out,4,c);
+        let mut st = SSCipherCore::new(&data,t_size, true, &mut out,4,c).unwrap();
         let start: Instant = Instant::now();
-        st.encrypt();
+        st.encrypt().unwrap();
         let duration = start.elapsed();
         println!(" Duration for Proposed with {}(128bit) 10KB (Lattence)=  {:?}",st.prp_cipher.name(), duration.as_micros());
         }
@@ -86,9 +210,9 @@ pub fn time_benchmark(){
     for c in CIPHER_128_NAMES {
         let t_size :usize =1073741824;
         let mut out = Vec::<GF128>::new();
-        let mut st = SSCipherCore::new(&data,t_size, true, &mut out,4,c);
+        let mut st = SSCipherCore::new(&data,t_size, true, &mut out,4,c).unwrap();
         let start: Instant = Instant::now();
-        st.encrypt();
+        st.encrypt().unwrap();
         let duration = start.elapsed();
         println!(" Duration for Proposed with {} (Throgput)=  {:?}",st.prp_cipher.name(), duration.as_secs());
         }
@@ -114,9 +238,9 @@ pub fn time_benchmark(){
     for c in CIPHER_256_NAMES {
         let t_size :usize =10240;
         let mut out = Vec::<GF128>::new();
-        let mut st = SSCipherCore::new(&data,t_size, true, &mut out,4,c);
+        let mut st = SSCipherCore::new(&data,t_size, true, &mut out,4,c).unwrap();
         let start: Instant = Instant::now();
-        st.encrypt();
+        st.encrypt().unwrap();
         let duration = start.elapsed();
         println!(" Duration for Proposed with {}(256bit) 10KB (Lattence)=  {:?}",st.prp_cipher.name(), duration.as_micros());
         }
@@ -141,9 +265,9 @@ pub fn time_benchmark(){
     for c in CIPHER_256_NAMES {
         let t_size :usize =1073741824;
         let mut out = Vec::<GF128>::new();
-        let mut st = SSCipherCore::new(&data,t_size, true, &mut out,4,c);
+        let mut st = SSCipherCore::new(&data,t_size, true, &mut out,4,c).unwrap();
         let start: Instant = Instant::now();
-        st.encrypt();
+        st.encrypt().unwrap();
         let duration = start.elapsed();
         println!(" Duration for Proposed with {} (Throgput)=  {:?}",st.prp_cipher.name(), duration.as_secs());
         }
@@ -165,15 +289,15 @@ pub fn sensitivity_bench( param :TestParam){
     let mut out2 = Vec::<GF128>::new();
     let key = GF128::random();
     let iv = GF128::random();
-    let mut st = SSCipherCore::new(&data,t_size, true, &mut out1,4,AES128 );
-    let mut st1 = SSCipherCore::new(&data,t_size, true, &mut out2,4,AES128 );
+    let mut st = SSCipherCore::new(&data,t_size, true, &mut out1,4,AES128 ).unwrap();
+    let mut st1 = SSCipherCore::new(&data,t_size, true, &mut out2,4,AES128 ).unwrap();
     let mut rng = rand::rng();
     for i in 0..128{
                 let mut diff :f32 = 0.0;
                 for _ in 0..1000{  
                             data.resize_with(t_size, || rng.random::<u8>());
-                            st.set_key_scheme(&[key], &iv);
-                            st.encrypt();
+                            st.set_key_scheme(&[key], &iv).unwrap();
+                            st.encrypt().unwrap();
                             let res1 = st.get_bytes_out();
                             let iv1;
                             let key1;
@@ -184,8 +308,8 @@ pub fn sensitivity_bench( param :TestParam){
                                                                 key1 = key; },
                                         }
 
-                            st1.set_key_scheme(&[key1], &iv1);
-                            st1.encrypt();
+                            st1.set_key_scheme(&[key1], &iv1).unwrap();
+                            st1.encrypt().unwrap();
                             let res2 = st1.get_bytes_out();        
                             diff = diff + bit_distances(t_size, res1, res2);
                         }
@@ -208,18 +332,18 @@ pub fn binomial_bench(){
     let mut out1 = Vec::<GF128>::new();
     let mut out2 = Vec::<GF128>::new();
     data.resize_with(t_size, || rng.random::<u8>());
-    let mut st = SSCipherCore::new(&data,t_size, true, &mut out1,4,AES128 );
-    let mut st1 = SSCipherCore::new(&data,t_size, true, &mut out2,4,AES128 );
+    let mut st = SSCipherCore::new(&data,t_size, true, &mut out1,4,AES128 ).unwrap();
+    let mut st1 = SSCipherCore::new(&data,t_size, true, &mut out2,4,AES128 ).unwrap();
     let mut v :[u128;8193]= [0;8193]; 
     for j in 0..10000{               
                         let key = GF128::random();           
                         for i in 0..128{
-                                st.set_key_scheme(&[key], &iv);
-                                st.encrypt();
+                                st.set_key_scheme(&[key], &iv).unwrap();
+                                st.encrypt().unwrap();
                                 let res1 = st.get_bytes_out();
                                 let key1 = key ^ GF128::from(1<< i);
-                                st1.set_key_scheme(&[key1], &iv);
-                                st1.encrypt();
+                                st1.set_key_scheme(&[key1], &iv).unwrap();
+                                st1.encrypt().unwrap();
                                 let res2 = st1.get_bytes_out();        
                                 let k= bit_diffrence(t_size, res1, res2);                            
                                 v[k as usize] =v [k as usize]+1;
@@ -248,27 +372,25 @@ pub fn basic_bench(){
         let iv = GF128::random();
         if level ==128{
                         let key = GF128::random();
-                        st = SSCipherCore::new(&data,targted_size, true, &mut out,4,c);
-                        st.set_key_scheme(&[key], &iv);
+                        st = SSCipherCore::with_key(&data,targted_size, true, &mut out,4,c,&[key], &iv).unwrap();
                         }
         else {
                 let key1 = GF128::random();
                 let key2 = GF128::random();
-                st = SSCipherCore::new(&data,targted_size, true, &mut out,4,c);
-                st.set_key_scheme(&[key1,key2], &iv);
+                st = SSCipherCore::with_key(&data,targted_size, true, &mut out,4,c,&[key1,key2], &iv).unwrap();
             }
 
         println!("{}", "-".repeat(100));
         println!("Benckmarking for the PRP '{}'",st.prp_cipher.name());
         // benchmark the proposed approach with the PRP
         let start: Instant = Instant::now();
-        st.encrypt();
+        st.encrypt().unwrap();
         let duration = start.elapsed();
         println!("Duration of the propsal = {:?}", duration);
         // Check Results of decryption correctness
-        st.decrypt();
+        let unpadded = st.decrypt().is_ok();
         let out = st.get_bytes_out();
-        let mut check =true;
+        let mut check = unpadded && out.len() == targted_size;
         for i in 0..targted_size{check &=out[i] == data[i]}
         println!("Check result correctness :{}",check);
         // benchmlark the CBC approach with the PRP
@@ -279,6 +401,640 @@ pub fn basic_bench(){
         let duration = start.elapsed();
         println!("Duration with CBC  = {:?}",  duration);
     }
+    rsbe_check();
+    padding_check();
+    full_parts_check();
+    aead_check();
+    short_messages_check();
+    mask_schedule_check();
+    max_depth_check();
+    level_plan_check();
+    container_check();
+    stream_check();
+    in_place_check();
+    kdf_check();
+    large_threshold_check();
+    gf_backend_check();
+    gf_constant_time_check();
+    batch_invert_check();
+    poly_check();
+    aes_backend_check();
+}
+
+// Rsbe facade : empty and 1 to 64*t bytes messages roundtrip, sequential and threaded, with each PRP width. A wrong
+// key never gives back the plaintext (and is reported when the container is authenticated or carries a key id), and
+// corrupted containers are rejected with a typed error
+pub fn rsbe_check(){
+    println!("{}", "-".repeat(100));
+    let mut rng = StdRng::seed_from_u64(1);
+    for (prp_name, key) in [(AES128, vec![GF128::random()]), (AES256, vec![GF128::random(), GF128::random()])] {
+        for t in [3usize, 4, 8] {
+            let mut rsbe = Rsbe::new(&key, prp_name, t).unwrap();
+            let mut check = rsbe.decrypt(&rsbe.encrypt(&[]).unwrap()).is_ok_and(|plaintext| plaintext.is_empty());
+            for threads in [1, 4] {
+                rsbe.set_threads(threads).unwrap();
+                for len in 1..=64 * t {
+                    let mut data = vec![0u8; len];
+                    rng.fill_bytes(&mut data);
+                    let container = rsbe.encrypt(&data).unwrap();
+                    check &= container.len() == RsbeHeader::parse(&container).unwrap().1 + (len / 16 + 1) * 16
+                             && rsbe.decrypt(&container).is_ok_and(|plaintext| plaintext == data);
+                }
+            }
+            println!("Rsbe {:?} with t = {}, empty and 1 to {} bytes, Check result correctness :{}", prp_name, t, 64 * t, check);
+        }
+    }
+    let check = (0..MIN_THRESHOLD).all(|t| fit_thresholds(100, t) == Err(RsbeError::ThresholdTooSmall) && LevelParams::top(100, t).is_err()
+                                           && Rsbe::new(&[GF128::random()], AES128, t).is_err());
+    println!("Thresholds below {} are rejected, Check result correctness :{}", MIN_THRESHOLD, check);
+    let key = [GF128::random()];
+    let mut rsbe = Rsbe::new(&key, AES128, 4).unwrap();
+    let mut other = Rsbe::new(&[key[0] + GF128::from(1)], AES128, 4).unwrap();
+    let data = vec![0x42u8; 1000];
+    let container = rsbe.encrypt(&data).unwrap();
+    let mut check = other.decrypt(&container).ok().as_ref() != Some(&data)
+                    && other.decrypt_aead(&rsbe.encrypt_aead(&data, b"").unwrap(), b"") == Err(RsbeError::AuthenticationFailed);
+    rsbe.set_key_id(b"key 1").unwrap();
+    other.set_key_id(b"key 2").unwrap();
+    check &= other.decrypt(&rsbe.encrypt(&data).unwrap()) == Err(RsbeError::KeyIdMismatch);
+    check &= rsbe.decrypt(&container) == Err(RsbeError::KeyIdMismatch) && rsbe.decrypt(&rsbe.encrypt(&data).unwrap()).is_ok_and(|plaintext| plaintext == data);
+    println!("Wrong keys never give back the plaintext, Check result correctness :{}", check);
+    let rsbe = Rsbe::new(&key, AES128, 4).unwrap();
+    let (_, header_size) = RsbeHeader::parse(&container).unwrap();
+    let mut corrupted = container.clone();
+    corrupted[5] = 0xee;
+    let mut check = rsbe.decrypt(&corrupted) == Err(RsbeError::UnknownCipher(0xee)) && rsbe.decrypt(&[]) == Err(RsbeError::InvalidHeader)
+                    && rsbe.decrypt(&container[..header_size]) == Err(RsbeError::InvalidLength)
+                    && rsbe.decrypt(&container[..container.len() - 1]) == Err(RsbeError::InvalidLength);
+    // Declared length with the same number of blocks as the actual one : caught once the padding is removed
+    let mut short = container[..header_size].to_vec();
+    short[26..34].copy_from_slice(&(data.len() as u64 - 1).to_be_bytes());
+    short.extend_from_slice(&container[header_size..]);
+    check &= rsbe.decrypt(&short) == Err(RsbeError::InvalidLength);
+    let mut flipped = container.clone();
+    flipped[header_size + 16] ^= 1;
+    check &= rsbe.decrypt(&flipped).ok().as_ref() != Some(&data);
+    println!("Corrupted containers are rejected, Check result correctness :{}", check);
+    // Errors are found before the first block is written : the data and the core are left as they were and the core
+    // can be used again
+    let data: Vec<u8> = (0..100 * 16).map(|i| i as u8).collect();
+    let mut out = Vec::<GF128>::new();
+    let mut st = SSCipherCore::with_key(&data, data.len(), false, &mut out, 4, AES128, &key, &GF128::random()).unwrap();
+    st.set_decode_solver(DecodeSolver::Inverses);
+    let top = format!("{:?}", st.active_level);
+    let rows = st.key_materials.vender_matrix.data.clone();
+    st.key_materials.vender_matrix.data.truncate(2);
+    let mut check = st.encrypt() == Err(RsbeError::InconsistentDimensions) && st.get_bytes_out() == &data[..];
+    st.key_materials.vender_matrix.data = rows.clone();
+    st.encrypt().unwrap();
+    let ciphertext = st.get_bytes_out().to_vec();
+    st.key_materials.vender_matrix.data[2] = rows[1].clone();      // Singular matrix, caught while preparing the inverses
+    check &= st.decrypt() == Err(RsbeError::MatrixNotInvertible) && st.get_bytes_out() == &ciphertext[..];
+    st.key_materials.vender_matrix.data = rows;
+    check &= st.decrypt().is_ok() && st.get_bytes_out() == &data[..] && format!("{:?}", st.active_level) == top;
+    println!("Failed encryption and decryption leave the data untouched, Check result correctness :{}", check);
+    // Rsbe keeps the key scheme of the last message : reused for the same PRP, IV and threshold only, the results must
+    // be the ones of a fresh instance
+    let rsbe = Rsbe::new(&key, AES128, 64).unwrap();
+    let data = vec![7u8; 4096];
+    let mut containers = vec![rsbe.encrypt(&data).unwrap(), rsbe.encrypt(&data).unwrap()];
+    let mut other = containers[0].clone();
+    other[8..10].copy_from_slice(&32u16.to_be_bytes());
+    containers.push(other.clone());
+    other[5] = AES256.id();
+    containers.push(other);
+    let mut check = true;
+    for i in [0, 0, 1, 0, 2, 0, 3, 2, 1, 1] {
+        let fresh = Rsbe::new(&key, AES128, 64).unwrap().decrypt(&containers[i]);
+        check &= rsbe.decrypt(&containers[i]) == fresh && (i > 1 || fresh.is_ok_and(|plaintext| plaintext == data));
+    }
+    println!("Key scheme kept across messages, Check result correctness :{}", check);
+    let start = Instant::now();
+    Rsbe::new(&key, AES128, 64).unwrap().decrypt(&containers[0]).unwrap();
+    let fresh = start.elapsed();
+    rsbe.decrypt(&containers[0]).unwrap();
+    let start = Instant::now();
+    rsbe.decrypt(&containers[0]).unwrap();
+    println!("Decryption of 4 KiB with t = 64 : {:?} from a fresh instance, {:?} with the kept key scheme", fresh, start.elapsed());
+}
+
+// Malformed padding in the last decrypted block (pad byte 0, pad byte above 16, pad bytes that disagree) must give
+// InvalidPadding with the RSBE, CBC and CTR cores, and every well-formed padding must be removed
+pub fn padding_check(){
+    println!("{}", "-".repeat(100));
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut last_blocks = Vec::new();
+    for pad in 1..=16u8 {
+        let mut block = [0u8; 16];
+        rng.fill_bytes(&mut block);
+        block[16 - pad as usize..].fill(pad);
+        last_blocks.push((block, Some(pad as usize)));
+        if pad > 1 {    // One pad byte differs, the first or the one before the last
+                        for position in [16 - pad as usize, 14] {
+                            let mut bad = block;
+                            bad[position] ^= 0x20;
+                            last_blocks.push((bad, None));
+                        }
+                    }
+    }
+    for pad in [0u8, 17, 0x80, 0xff] {
+        let mut block = [pad; 16];
+        block[0] = rng.random();
+        last_blocks.push((block, None));
+        last_blocks.push(([pad; 16], None));
+    }
+    let expected = |padding :Option<usize>| padding.map_or(Err(RsbeError::InvalidPadding), |pad| Ok(48 - pad));
+    let mut checks = [true; 3];
+    for (last_block, padding) in &last_blocks {
+        let mut data = vec![0u8; 48];
+        rng.fill_bytes(&mut data[..32]);
+        data[32..].copy_from_slice(last_block);
+        let mut out = Vec::<GF128>::new();
+        let mut st = SSCipherCore::new(&data, data.len(), false, &mut out, 3, AES128).unwrap();
+        st.encrypt().unwrap();
+        st.padded = true;
+        checks[0] &= st.decrypt().map(|_| st.get_bytes_out().len()) == expected(*padding);
+        let mut out = Vec::<u128>::new();
+        let mut st = CBCCipherCore::new(&data, data.len(), false, &mut out, AES128);
+        st.encrypt();
+        st.padded = true;
+        checks[1] &= st.decrypt().map(|_| st.get_bytes_out().len()) == expected(*padding);
+        let mut out = Vec::<u128>::new();
+        let mut st = CTRCipherCore::new(&data, data.len(), false, &mut out, AES128);
+        st.encrypt();
+        st.padded = true;
+        checks[2] &= st.decrypt().map(|_| st.get_bytes_out().len()) == expected(*padding);
+    }
+    for (core, check) in ["RSBE", "CBC", "CTR"].iter().zip(checks) {
+        println!("Malformed padding with the {} core ({} last blocks), Check result correctness :{}", core, last_blocks.len(), check);
+    }
+}
+
+// Levels whose parts are all full, as 6 blocks at t = 3 : the last part must be encoded too, so no ciphertext block may
+// equal the plaintext block at the same position, and the decryption must give back the data
+pub fn full_parts_check(){
+    println!("{}", "-".repeat(100));
+    let mut rng = StdRng::seed_from_u64(3);
+    for t in [3usize, 4, 8] {
+        let (mut check, mut sizes) = (true, 0);
+        for blocks_count in 2 * t - 1..=16 * t {
+            let mut data = vec![0u8; blocks_count * 16];
+            rng.fill_bytes(&mut data);
+            let mut out = Vec::<GF128>::new();
+            let mut st = SSCipherCore::new(&data, data.len(), false, &mut out, t, AES128).unwrap();
+            let level = st.active_level;
+            if level.max_part_size < 2 || level.blocks_count != level.threshold * level.max_part_size {continue}
+            sizes += 1;
+            check &= level.last_part_size == level.max_part_size;
+            st.encrypt().unwrap();
+            for (plain, cipher) in data.chunks_exact(16).zip(st.get_bytes_out().chunks_exact(16)) {check &= plain != cipher}
+            check &= st.decrypt().is_ok() && st.get_bytes_out() == &data[..];
+        }
+        check &= sizes > 0;
+        println!("Levels with all their parts full ({} sizes) with t = {}, Check result correctness :{}", sizes, t, check);
+    }
+}
+
+// Authenticated containers : roundtrip with and without associated data, and any flipped bit of the header, ciphertext
+// or tag, a different associated data or a truncated tag is rejected without plaintext. The tag is computed under the
+// derived MAC key and not the RSBE PRP
+pub fn aead_check(){
+    println!("{}", "-".repeat(100));
+    let key = [GF128::random(), GF128::random()];
+    let rsbe = Rsbe::new(&key, AES256, 4).unwrap();
+    let mut rng = StdRng::seed_from_u64(8);
+    let mut check = true;
+    for len in [0usize, 1, 16, 100, 1000] {
+        let mut data = vec![0u8; len];
+        rng.fill_bytes(&mut data);
+        for associated_data in [&b""[..], b"object 42", &[0xa5; 100]] {
+            let sealed = rsbe.encrypt_aead(&data, associated_data).unwrap();
+            check &= rsbe.decrypt_aead(&sealed, associated_data).is_ok_and(|plaintext| plaintext == data);
+            for bit in 0..sealed.len() * 8 {
+                let mut tampered = sealed.clone();
+                tampered[bit / 8] ^= 1 << (bit % 8);
+                check &= rsbe.decrypt_aead(&tampered, associated_data).is_err();
+            }
+            let mut longer = associated_data.to_vec();
+            longer.push(0);
+            check &= rsbe.decrypt_aead(&sealed, &longer) == Err(RsbeError::AuthenticationFailed);
+            for i in 0..associated_data.len() {
+                let mut flipped = associated_data.to_vec();
+                flipped[i] ^= 0x80;
+                check &= rsbe.decrypt_aead(&sealed, &flipped) == Err(RsbeError::AuthenticationFailed);
+            }
+            check &= rsbe.decrypt_aead(&sealed[..sealed.len() - 1], associated_data).is_err();
+        }
+    }
+    println!("Authenticated containers, every bit flipped, Check result correctness :{}", check);
+    let prp = common_ciphers::CommonCipher::newcipher(&AES256, &[key[0].to_u128(), key[1].to_u128()]);
+    let sealed = rsbe.encrypt_aead(b"some data", b"ad").unwrap();
+    let (header, header_size) = RsbeHeader::parse(&sealed).unwrap();
+    let (body, tag) = sealed[header_size..].split_at(sealed.len() - header_size - 16);
+    // Same MAC keyed with the RSBE PRP itself
+    let mut mac = PolyMac::new(&prp);
+    mac.update_associated_data(&sealed[..header_size]);
+    mac.update_associated_data(b"ad");
+    mac.update_ciphertext(body);
+    let check = *tag == compute_tag(&prp, AES256, &header.iv, &[&sealed[..header_size], b"ad"], body).unwrap()
+                && *tag != mac.finalize(&header.iv, &prp);
+    println!("Tags under the derived MAC key, Check result correctness :{}", check);
+}
+
+// Messages of 1 to 64*t bytes, from inputs too short for a Shamir level to a few levels, including levels whose parts
+// are all full : no ciphertext block (padding block included) may equal the plaintext block at the same position, and
+// the decryption must give back the message
+pub fn short_messages_check(){
+    println!("{}", "-".repeat(100));
+    for t in [3usize, 4, 8, 15] {
+        let mut check = true;
+        let mut rng = StdRng::seed_from_u64(t as u64);
+        for len in 1..=64 * t {
+            let mut data = vec![0u8; len];
+            rng.fill_bytes(&mut data);
+            let mut out = Vec::<GF128>::new();
+            let mut st = SSCipherCore::new(&data, len, true, &mut out, t, AES128).unwrap();
+            st.encrypt().unwrap();
+            let mut padded = data.clone();
+            padded.resize((len / 16 + 1) * 16, (16 - len % 16) as u8);
+            for (plain, cipher) in padded.chunks_exact(16).zip(st.get_bytes_out().chunks_exact(16)) {check &= plain != cipher}
+            check &= st.decrypt().is_ok() && st.get_bytes_out() == &data[..];
+        }
+        println!("Short messages (1 to {} bytes) with t = {:>2}, Check result correctness :{}", 64 * t, t, check);
+    }
+}
+
+// Masks of the vectors of a level : with the Generator schedule no (alpha, beta) pair repeats over the first 1024
+// vectors, whereas the Frobenius schedule repeats every 128 vectors. Messages encrypted with either schedule, on the
+// sequential and parallel paths, must decrypt back, and the two schedules must give different ciphertexts
+pub fn mask_schedule_check(){
+    println!("{}", "-".repeat(100));
+    let key = GF128::random();
+    let iv = GF128::random();
+    let t = 8;
+    let prp = common_ciphers::CommonCipher::newcipher(&AES128, &[key.to_u128(), key.to_u128()]);
+    let mut key_scheme = generate_key_scheme(&[key], &iv, t, &prp).unwrap();
+    for (schedule, period) in [(MaskSchedule::Generator, None), (MaskSchedule::Frobenius, Some(128))] {
+        key_scheme.mask_schedule = schedule;
+        let masks: Vec<(u128, Vec<u128>)> = (0..1024).map(|i| { let (alpha, beta_vector) = key_scheme.masks_at(t, i);
+                                                                (alpha.to_u128(), beta_vector.elements[..t].iter().map(|b| b.to_u128()).collect())
+                                                              }).collect();
+        let mut check = true;
+        for i in 0..masks.len() {
+            for j in i + 1..masks.len() {check &= (masks[i] == masks[j]) == period.is_some_and(|p| (j - i).is_multiple_of(p))}
+        }
+        let expected = period.map_or("never repeat".to_string(), |p| format!("repeat every {} vectors", p));
+        println!("Masks of 1024 vectors with {:?} schedule {}, Check result correctness :{}", schedule, expected, check);
+    }
+    let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let mut data = vec![0u8; 1 << 20];
+    rand::rng().fill_bytes(&mut data);
+    let mut ciphertexts = Vec::new();
+    for schedule in [MaskSchedule::Generator, MaskSchedule::Frobenius] {
+        let mut check = true;
+        for parallel in [false, true] {
+            let mut out = Vec::<GF128>::new();
+            let mut st = SSCipherCore::with_key(&data, data.len(), true, &mut out, t, AES128, &[key], &iv).unwrap();
+            st.set_mask_schedule(schedule);
+            if parallel {st.par_encrypt(&pool).unwrap()} else {st.encrypt().unwrap()}
+            ciphertexts.push(st.get_bytes_out().to_vec());
+            if parallel {st.par_decrypt(&pool).unwrap()} else {st.decrypt().unwrap()}
+            check &= st.get_bytes_out() == &data[..];
+        }
+        println!("Roundtrip of 1 MiB with {:?} schedule (sequential and parallel), Check result correctness :{}", schedule, check);
+    }
+    let check = ciphertexts[0] == ciphertexts[1] && ciphertexts[2] == ciphertexts[3] && ciphertexts[0] != ciphertexts[2];
+    println!("Sequential and parallel ciphertexts agree, schedules differ, Check result correctness :{}", check);
+    // SSCipherCore keeps the original schedule by default, it survives a rebuild of the key scheme
+    let mut out = Vec::<GF128>::new();
+    let mut st = SSCipherCore::with_key(&data, data.len(), true, &mut out, t, AES128, &[key], &iv).unwrap();
+    let default_schedule = st.key_materials.mask_schedule;
+    st.set_mask_schedule(MaskSchedule::Generator);
+    st.set_key_scheme(&[key], &iv).unwrap();
+    st.encrypt().unwrap();
+    let check = default_schedule == MaskSchedule::Frobenius && st.key_materials.mask_schedule == MaskSchedule::Generator
+                && st.get_bytes_out() == &ciphertexts[0][..];
+    println!("Frobenius by default, mask schedule kept by set_key_scheme, Check result correctness :{}", check);
+}
+
+// Recursion capped at 1 to 4 levels : roundtrip on the sequential and parallel paths, and a cap at least equal to the
+// natural depth must give the same ciphertext as the uncapped recursion
+pub fn max_depth_check(){
+    println!("{}", "-".repeat(100));
+    let key = GF128::random();
+    let iv = GF128::random();
+    let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let mut data = vec![0u8; 1 << 20];
+    rand::rng().fill_bytes(&mut data);
+    let encrypt = |max_depth :Option<usize>, parallel :bool| {
+        let mut out = Vec::<GF128>::new();
+        let mut st = SSCipherCore::with_key(&data, data.len(), true, &mut out, 4, AES128, &[key], &iv).unwrap();
+        st.set_max_depth(max_depth).unwrap();
+        let start = Instant::now();
+        if parallel {st.par_encrypt(&pool).unwrap()} else {st.encrypt().unwrap()}
+        let elapsed = start.elapsed();
+        let ciphertext = st.get_bytes_out().to_vec();
+        if parallel {st.par_decrypt(&pool).unwrap()} else {st.decrypt().unwrap()}
+        (ciphertext, st.get_bytes_out() == &data[..], elapsed)
+    };
+    let (reference, _, _) = encrypt(None, false);
+    for depth in 1..=4 {
+        let (ciphertext, sequential_ok, elapsed) = encrypt(Some(depth), false);
+        let (par_ciphertext, parallel_ok, _) = encrypt(Some(depth), true);
+        let check = sequential_ok && parallel_ok && ciphertext == par_ciphertext && ciphertext != reference;
+        println!("Max depth {} on 1 MiB, encryption in {:>8.3} ms, Check result correctness :{}", depth, elapsed.as_secs_f64() * 1000.0, check);
+    }
+    let (ciphertext, check, _) = encrypt(Some(MAX_DEPTH), false);
+    println!("Max depth {} gives the uncapped ciphertext, Check result correctness :{}", MAX_DEPTH, check && ciphertext == reference);
+}
+
+// Level plans : the vectors of each level must cover exactly its blocks, each level holds the first part of the one
+// above, and the plan must follow the core built for the same message
+pub fn level_plan_check(){
+    println!("{}", "-".repeat(100));
+    let mut check = true;
+    for t in [3usize, 4, 8, 15] {
+        for blocks_count in (1..5000).chain([1 << 16, 1 << 20]) {
+            for max_depth in [None, Some(1), Some(2)] {
+                let plan = level_plan(blocks_count, t, max_depth).unwrap();
+                let mut expected_blocks = blocks_count;
+                for summary in &plan.levels {
+                    let level = &summary.params;
+                    check &= level.blocks_count == expected_blocks && summary.principal_vectors + summary.secondary_vectors == level.max_part_size
+                             && level.last_part_size >= 1 && level.last_part_size <= level.max_part_size
+                             && summary.principal_vectors * level.threshold + summary.secondary_vectors * (level.threshold - 1) == level.blocks_count;
+                    expected_blocks = level.max_part_size;
+                }
+                check &= plan.levels.len() <= max_depth.unwrap_or(usize::MAX) && plan.terminal_blocks >= 1 && plan.terminal_blocks <= expected_blocks;
+            }
+        }
+    }
+    println!("Level plans for t in [3, 4, 8, 15] and 1 to 5000 blocks, Check result correctness :{}", check);
+    let data = vec![0u8; 1 << 20];
+    let mut out = Vec::<GF128>::new();
+    let st = SSCipherCore::new(&data, data.len(), true, &mut out, 4, AES128).unwrap();
+    let plan = st.level_plan().unwrap();
+    println!("{}", plan);
+    println!("Plan of the core for 1 MiB, Check result correctness :{}", plan.blocks_count == (1 << 16) + 1);
+    // Interleaved saves t - 1 products per vector for t*t per level, the Frobenius schedule always counts Single
+    let mut check = true;
+    for t in [3usize, 4, 8, 15] {
+        for blocks_count in [2 * t - 1, 1000, 1 << 16] {
+            let plan_of = |kernel, mask_schedule| level_plan_with(blocks_count, t, None, kernel, mask_schedule).unwrap();
+            let (single, interleaved) = (plan_of(EncodeKernel::Single, MaskSchedule::Generator), plan_of(EncodeKernel::Interleaved, MaskSchedule::Generator));
+            let frobenius = plan_of(EncodeKernel::Interleaved, MaskSchedule::Frobenius);
+            check &= frobenius.kernel == EncodeKernel::Single && frobenius.gf_multiplications == single.gf_multiplications
+                     && level_plan(blocks_count, t, None).unwrap().gf_multiplications == interleaved.gf_multiplications;
+            for (s, i) in single.levels.iter().zip(&interleaved.levels) {
+                let (level_t, vectors) = (s.params.threshold, s.params.max_part_size);
+                check &= s.gf_multiplications + level_t * level_t == i.gf_multiplications + vectors * (level_t - 1);
+            }
+        }
+    }
+    println!("Costs of the Single and Interleaved kernels, Check result correctness :{}", check);
+}
+
+// Headers with every combination of optional fields must parse back to themselves with their encoded size, and a
+// tampered header (lengths, flags, sizes) must be rejected with a typed error before any decryption
+pub fn container_check(){
+    println!("{}", "-".repeat(100));
+    let mut check = true;
+    for key_id in [None, Some(b"tenant-7".to_vec()), Some(vec![0xa5; 255])] {
+        for authenticated in [false, true] {
+            for max_depth in [None, Some(1), Some(MAX_DEPTH)] {
+                for mask_schedule in [MaskSchedule::Frobenius, MaskSchedule::Generator] {
+                    let mut header = RsbeHeader::new(Camellia256bit, 1024, GF128::random(), 123456789, key_id.clone());
+                    header.authenticated = authenticated;
+                    header.max_depth = max_depth;
+                    header.mask_schedule = mask_schedule;
+                    let bytes = header.to_bytes().unwrap();
+                    check &= bytes.len() == header.size() && RsbeHeader::parse(&bytes) == Ok((header, bytes.len()));
+                }
+            }
+        }
+    }
+    println!("Header write and parse roundtrip, Check result correctness :{}", check);
+    let rsbe = Rsbe::new(&[GF128::random()], AES128, 3).unwrap();
+    let container = rsbe.encrypt(&[7u8; 100]).unwrap();
+    let header_size = RsbeHeader::parse(&container).unwrap().1;
+    let tampered = |offset :usize, value :u8| {let mut bytes = container.clone(); bytes[offset] = value; rsbe.decrypt(&bytes)};
+    let with_length = |length :u64| {let mut bytes = container[..header_size].to_vec(); bytes[26..34].copy_from_slice(&length.to_be_bytes()); bytes};
+    let mut check = tampered(0, b'X') == Err(RsbeError::InvalidHeader) && tampered(4, 2) == Err(RsbeError::UnsupportedVersion(2))
+                    && tampered(6, 0x08) == Err(RsbeError::InvalidHeader)
+                    && tampered(7, 2) == Err(RsbeError::InvalidHeader) && tampered(8, 0xff) == Err(RsbeError::ThresholdTooLarge)
+                    && rsbe.decrypt(&container[..header_size - 1]) == Err(RsbeError::InvalidHeader)
+                    && rsbe.decrypt(&container[..container.len() - 16]) == Err(RsbeError::InvalidLength)
+                    && rsbe.decrypt(&[&container[..], &[0u8; 16]].concat()) == Err(RsbeError::InvalidLength);
+    // Plaintext lengths whose ciphertext size overflows must not wrap to a size matching a short body
+    for length in [u64::MAX, u64::MAX - 15, u64::MAX / 16 * 16] {
+        let bytes = with_length(length);
+        check &= RsbeHeader::parse(&bytes).err() == Some(RsbeError::InvalidHeader) && rsbe.decrypt(&bytes) == Err(RsbeError::InvalidHeader);
+    }
+    check &= rsbe.decrypt(&[&with_length(50)[..], &container[header_size..]].concat()) == Err(RsbeError::InvalidLength);
+    println!("Tampered headers are rejected, Check result correctness :{}", check);
+}
+
+// Streams of 0 to 5 segments around the segment boundaries must decrypt back. A stream cut anywhere must be rejected,
+// and frames dropped, duplicated or swapped must never give back the plaintext. Segment sizes above MAX_SEGMENT_SIZE
+// are refused by the writer and, from a forged header, by the reader before anything is allocated
+pub fn stream_check(){
+    println!("{}", "-".repeat(100));
+    let key = [GF128::random()];
+    let mut rng = StdRng::seed_from_u64(6);
+    let mut check = true;
+    for segment_size in [16usize, 160, 4096] {
+        for len in [0, 1, 15, 16, 17, segment_size - 1, segment_size, segment_size + 1, 3 * segment_size, 5 * segment_size - 7] {
+            let mut data = vec![0u8; len];
+            rng.fill_bytes(&mut data);
+            let mut stream = Vec::new();
+            check &= encrypt_stream(&mut &data[..], &mut stream, &key, AES128, 4, segment_size).unwrap() == len as u64;
+            let mut plaintext = Vec::new();
+            check &= decrypt_stream(&stream[..], &mut plaintext, &key).unwrap() == len as u64 && plaintext == data;
+        }
+    }
+    println!("Stream roundtrip of 0 to 5 segments, Check result correctness :{}", check);
+    let segment_size = 64;
+    let mut data = vec![0u8; 4 * segment_size + 20];
+    rng.fill_bytes(&mut data);
+    let mut stream = Vec::new();
+    encrypt_stream(&mut &data[..], &mut stream, &key, AES128, 4, segment_size).unwrap();
+    let decrypt = |stream :&[u8]| {let mut plaintext = Vec::new(); decrypt_stream(stream, &mut plaintext, &key).map(|_| plaintext)};
+    let mut check = (0..stream.len()).all(|cut| decrypt(&stream[..cut]).is_err());
+    let mut frames = Vec::new();
+    let mut position = 30;
+    while position < stream.len() {
+        let length = u32::from_be_bytes(stream[position + 1..position + 5].try_into().unwrap()) as usize;
+        frames.push(&stream[position..position + 5 + length]);
+        position += 5 + length;
+    }
+    let reassemble = |order :&[usize]| [&stream[..30]].into_iter().chain(order.iter().map(|&i| frames[i])).collect::<Vec<_>>().concat();
+    for order in [vec![1, 0, 2, 3, 4], vec![0, 1, 3, 2, 4], vec![0, 1, 2, 4, 3], vec![0, 2, 3, 4], vec![0, 1, 2, 3], vec![0, 0, 1, 2, 3, 4], vec![0, 1, 2, 3, 4, 4]] {
+        check &= decrypt(&reassemble(&order)).ok().as_ref() != Some(&data);
+    }
+    check &= decrypt(&reassemble(&[0, 1, 2, 3, 4])).is_ok_and(|plaintext| plaintext == data);
+    println!("Truncated and reordered streams are rejected, Check result correctness :{}", check);
+    let new_encryptor = |segment_size :usize| StreamEncryptor::new(Vec::new(), &key, AES128, 4, segment_size, &GF128::random(), MaskSchedule::Generator, None);
+    let mut check = new_encryptor(MAX_SEGMENT_SIZE + 16).is_err() && new_encryptor(MAX_SEGMENT_SIZE).is_ok();
+    for segment_size in [MAX_SEGMENT_SIZE as u32 + 16, u32::MAX - 15] {
+        let mut forged = stream.clone();
+        forged[10..14].copy_from_slice(&segment_size.to_be_bytes());
+        check &= decrypt(&forged).is_err_and(|e| e.get_ref().and_then(|e| e.downcast_ref::<RsbeError>()) == Some(&RsbeError::InvalidHeader));
+    }
+    println!("Segment sizes above {} bytes are rejected, Check result correctness :{}", MAX_SEGMENT_SIZE, check);
+    // Mask schedule and recursion depth chosen by the writer, recorded in the header and followed by the reader
+    // Segments of 256 blocks go through several levels, so the depth cap changes the ciphertext
+    let segment_size = 4096;
+    let mut data = vec![0u8; 2 * segment_size + 20];
+    rng.fill_bytes(&mut data);
+    let data = &data[..];
+    let mut check = true;
+    for mask_schedule in [MaskSchedule::Frobenius, MaskSchedule::Generator] {
+        for max_depth in [None, Some(1), Some(2)] {
+            let master_iv = GF128::random();
+            let mut encryptor = StreamEncryptor::new(Vec::new(), &key, AES128, 4, segment_size, &master_iv, mask_schedule, max_depth).unwrap();
+            encryptor.write_all(data).unwrap();
+            let stream = encryptor.finish().unwrap();
+            check &= stream[6] == mask_schedule.id() && stream[7] == max_depth.unwrap_or(0) as u8
+                     && decrypt(&stream).is_ok_and(|plaintext| plaintext == data);
+            // The same stream read with another schedule or depth must not give the plaintext back
+            let mut other = stream.clone();
+            other[6] ^= 1;
+            check &= decrypt(&other).ok().as_deref() != Some(data);
+            other[6] ^= 1;
+            other[7] = if max_depth == Some(1) {2} else {1};
+            check &= decrypt(&other).ok().as_deref() != Some(data);
+        }
+    }
+    check &= new_encryptor(16).is_ok() && StreamEncryptor::new(Vec::new(), &key, AES128, 4, 16, &GF128::random(), MaskSchedule::Generator, Some(0)).is_err()
+             && StreamEncryptor::new(Vec::new(), &key, AES128, 4, 16, &GF128::random(), MaskSchedule::Generator, Some(MAX_DEPTH + 1)).is_err();
+    println!("Stream mask schedules and recursion depths, Check result correctness :{}", check);
+}
+
+// In place encryption of aligned buffers of 1 to 64*t blocks : roundtrip through Rsbe, same ciphertext as the copying
+// constructor without padding, and buffers that are not a whole number of blocks or not aligned are rejected untouched
+pub fn in_place_check(){
+    println!("{}", "-".repeat(100));
+    let key = GF128::random();
+    let mut rng = StdRng::seed_from_u64(9);
+    let mut storage = vec![0u8; 64 * 8 * 16 + 32];
+    let offset = storage.as_ptr().align_offset(16);
+    for t in [3usize, 4, 8] {
+        let rsbe = Rsbe::new(&[key], AES128, t).unwrap();
+        let mut check = true;
+        for blocks_count in 1..=64 * t {
+            let len = blocks_count * 16;
+            let buffer = &mut storage[offset..offset + len];
+            rng.fill_bytes(buffer);
+            let data = buffer.to_vec();
+            let iv = rsbe.encrypt_in_place(buffer).unwrap();
+            for (plain, cipher) in data.chunks_exact(16).zip(buffer.chunks_exact(16)) {check &= plain != cipher}
+            let mut out = Vec::<GF128>::new();
+            let mut st = SSCipherCore::with_key(&data, len, false, &mut out, t, AES128, &[key], &iv).unwrap();
+            st.set_mask_schedule(MaskSchedule::Generator);
+            st.encrypt().unwrap();
+            check &= st.get_bytes_out() == &buffer[..];
+            rsbe.decrypt_in_place(buffer, &iv).unwrap();
+            check &= buffer == &data[..];
+        }
+        println!("In place roundtrip of 1 to {} blocks with t = {}, Check result correctness :{}", 64 * t, t, check);
+    }
+    let rsbe = Rsbe::new(&[key], AES128, 3).unwrap();
+    let mut check = true;
+    for len in [0usize, 1, 15, 17, 100, 255] {
+        let buffer = &mut storage[offset..offset + len];
+        buffer.fill(7);
+        check &= rsbe.encrypt_in_place(buffer) == Err(RsbeError::InvalidLength) && buffer.iter().all(|&b| b == 7)
+                 && rsbe.decrypt_in_place(buffer, &GF128::random()) == Err(RsbeError::InvalidLength);
+    }
+    let buffer = &mut storage[offset + 8..offset + 8 + 64];
+    buffer.fill(7);
+    check &= rsbe.encrypt_in_place(buffer) == Err(RsbeError::Misaligned) && buffer.iter().all(|&b| b == 7);
+    println!("Partial blocks and misaligned buffers are rejected, Check result correctness :{}", check);
+}
+
+// AES-128 CMAC against the RFC 4493 (SP 800-38B) examples, whole and fed in pieces. derive_keys and passphrase_kdf
+// must be deterministic and give unrelated keys for another label, context, master key, passphrase, salt or cost
+pub fn kdf_check(){
+    println!("{}", "-".repeat(100));
+    let prp = common_ciphers::CommonCipher::newcipher(&AES128, &[0x2b7e151628aed2a6abf7158809cf4f3c, 0x2b7e151628aed2a6abf7158809cf4f3c]);
+    let message = [0x6bc1bee22e409f96e93d7e117393172a_u128, 0xae2d8a571e03ac9c9eb76fac45af8e51, 0x30c81c46a35ce411e5fbc1191a0a52ef,
+                   0xf69f2445df4f9b17ad2b417be66c3710].iter().flat_map(|block| block.to_be_bytes()).collect::<Vec<u8>>();
+    let vectors = [(0usize, 0xbb1d6929e95937287fa37d129b756746_u128), (16, 0x070a16b46b4d4144f79bdd9dd04a287c),
+                   (40, 0xdfa66747de9ae63030ca32611497c827), (64, 0x51f0bebf7e3b9d92fc49741779363cfe)];
+    let mut check = true;
+    for (len, tag) in vectors {
+        check &= cmac(&prp, &[&message[..len]]) == tag;
+        for split in 0..=len {check &= cmac(&prp, &[&message[..split], &[], &message[split..len]]) == tag}
+    }
+    println!("AES-128 CMAC RFC 4493 examples (0, 16, 40 and 64 bytes), Check result correctness :{}", check);
+    let master = [GF128::random(), GF128::random()];
+    let derive = |master :&[GF128], label :&[u8], context :&[u8]| derive_keys(master, AES256, label, context, 3).unwrap();
+    let reference = derive(&master, b"RSBE object", b"bucket/a");
+    let others = [derive(&master, b"RSBE objecT", b"bucket/a"), derive(&master, b"RSBE object", b"bucket/b"),
+                  derive(&master, b"RSBE object", b""), derive(&master, b"RSBE object\0bucket/", b"a"),
+                  derive(&[master[0], master[1] + GF128::from(1)], b"RSBE object", b"bucket/a")];
+    let distinct = |a :&[GF128], b :&[GF128]| a.iter().all(|x| b.iter().all(|y| x.to_u128() != y.to_u128()));
+    let mut check = reference.len() == 3 && derive(&master, b"RSBE object", b"bucket/a").iter().zip(&reference).all(|(a, b)| a.to_u128() == b.to_u128())
+                    && distinct(&reference[..1], &reference[1..]) && reference[1].to_u128() != reference[2].to_u128();
+    for other in &others {check &= distinct(&reference, other)}
+    check &= derive_keys(&[], AES256, b"RSBE object", b"", 1) == Err(RsbeError::InvalidKey)
+             && derive_keys(&master, AES256, b"RSBE object", b"", 0) == Err(RsbeError::InvalidKdfParameters);
+    println!("derive_keys deterministic and separated by label, context and key, Check result correctness :{}", check);
+    let params = PassphraseParams { memory_blocks: 64, iterations: 1, prp_name: AES128 };
+    let salt = [0x5a; MIN_SALT_SIZE];
+    let kdf = |passphrase :&[u8], salt :&[u8], params :&PassphraseParams| passphrase_kdf(passphrase, salt, params, 2).unwrap();
+    let reference = kdf(b"correct horse", &salt, &params);
+    let mut other_salt = salt;
+    other_salt[MIN_SALT_SIZE - 1] ^= 1;
+    let others = [kdf(b"correct horsf", &salt, &params), kdf(b"correct horse", &other_salt, &params),
+                  kdf(b"correct horse", &[&salt[..], b"x"].concat(), &params), kdf(b"correct horse", &salt, &PassphraseParams { iterations: 2, ..params }),
+                  kdf(b"correct horse", &salt, &PassphraseParams { memory_blocks: 65, ..params }),
+                  kdf(b"correct horse", &salt, &PassphraseParams { prp_name: AES256, ..params })];
+    let mut check = kdf(b"correct horse", &salt, &params).iter().zip(&reference).all(|(a, b)| a.to_u128() == b.to_u128());
+    for other in &others {check &= distinct(&reference, other)}
+    check &= passphrase_kdf(b"correct horse", &salt[1..], &params, 2) == Err(RsbeError::InvalidKdfParameters);
+    println!("passphrase_kdf deterministic and separated by passphrase, salt and cost, Check result correctness :{}", check);
+}
+
+// Thresholds above the inline capacity of the vectors (heap backed path) : roundtrip, sequential and parallel, of
+// messages around the 2t-1 blocks boundary and of larger ones, and inverses kept for sizes beyond the packed ones
+pub fn large_threshold_check(){
+    println!("{}", "-".repeat(100));
+    let key = GF128::random();
+    let iv = GF128::random();
+    let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+    let mut rng = StdRng::seed_from_u64(256);
+    for t in [16usize, 31, 64, 128, 256] {
+        let mut check = true;
+        let start = Instant::now();
+        for blocks in [1, t - 1, 2 * t - 2, 2 * t - 1, 2 * t, 3 * t + 1, t * t / 2 + 7, 4096] {
+            let mut data = vec![0u8; blocks * 16 - 1];
+            rng.fill_bytes(&mut data);
+            for parallel in [false, true] {
+                let mut out = Vec::<GF128>::new();
+                let mut st = SSCipherCore::with_key(&data, data.len(), true, &mut out, t, AES128, &[key], &iv).unwrap();
+                if parallel {st.par_encrypt(&pool).unwrap()} else {st.encrypt().unwrap()}
+                for (plain, cipher) in data.chunks_exact(16).zip(st.get_bytes_out().chunks_exact(16)) {check &= plain != cipher}
+                if parallel {st.par_decrypt(&pool).unwrap()} else {st.decrypt().unwrap()}
+                check &= st.get_bytes_out() == &data[..];
+            }
+        }
+        println!("Threshold t = {:>3} ({:?}), Check result correctness :{}", t, start.elapsed(), check);
+    }
+    let mut points = GF128Vector::new(90);
+    for i in 0..90 {points.elements[i] = GF128::random()}
+    let matrix = GF128Matrix::vandermonde(&points);
+    let mut inverses = VandermondeInverses::default();
+    let mut check = true;
+    for sizes in [vec![40, 39], vec![70], vec![35, 45], vec![90, 12]] {
+        inverses.require(&matrix, &sizes).unwrap();
+        for size in sizes {
+            let expected = matrix.invert_vandermonde(size).unwrap();
+            let found = inverses.get(size).unwrap();
+            for i in 0..size {for j in 0..size {check &= expected.data[i][j] == found[i * size + j]}}
+        }
+    }
+    println!("Inverses of Vandermonde prefixes above the packed sizes, Check result correctness :{}", check);
 }
 
 pub fn parallel_bench(){
@@ -373,4 +1129,320 @@ pub fn parallel_bench(){
                                             );
                                         }
     }
-}
\ No newline at end of file
+    rsbe_parallel_bench();
+}
+
+// Level-parallel RSBE encryption, the output must be identical to the sequential one
+pub fn rsbe_parallel_bench(){
+    let data_size = 16 * 1024 * 1024;
+    let mut data = vec![0u8; data_size];
+    StdRng::seed_from_u64(7).fill_bytes(&mut data);
+    let key = GF128::random();
+    let iv = GF128::random();
+    let mut out = Vec::<GF128>::new();
+    let mut st = SSCipherCore::with_key(&data,data_size, true, &mut out,4,AES128,&[key], &iv).unwrap();
+    let start = Instant::now();
+    st.encrypt().unwrap();
+    println!("--- RSBE (AES128, t=4) on {} bytes, sequential : {:?}", data_size, start.elapsed());
+    let reference = st.get_bytes_out().to_vec();
+    for &t in &[2usize, 4, 8, 16] {
+        let pool = ThreadPoolBuilder::new().num_threads(t).build().unwrap();
+        let mut out = Vec::<GF128>::new();
+        let mut st = SSCipherCore::with_key(&data,data_size, true, &mut out,4,AES128,&[key], &iv).unwrap();
+        let start = Instant::now();
+        st.par_encrypt(&pool).unwrap();
+        let duration = start.elapsed();
+        let mut check = st.get_bytes_out() == &reference[..];
+        st.par_decrypt(&pool).unwrap();
+        check &= st.get_bytes_out() == &data[..];
+        println!("Threads: {:>2} | Time: {:>10.4?} | Check result correctness :{}", t, duration, check);
+    }
+}
+
+// Bit by bit product modulo x^128 + x^7 + x^2 + x + 1, reference of the carry-less multiply backends
+fn reference_mul(a :u128, b :u128) -> u128 {
+    let (mut a, mut result) = (a, 0u128);
+    for i in 0..128 {
+        if (b >> i) & 1 == 1 {result ^= a}
+        a = (a << 1) ^ if a >> 127 == 1 {0x87} else {0};
+    }
+    result
+}
+
+// The portable and pclmul backends must give the same products, dot products and inverses, and agree with the bit by
+// bit reference. Times 1M products with each available backend
+pub fn gf_backend_check(){
+    println!("{}", "-".repeat(100));
+    let backends: Vec<Backend> = [Backend::Portable, Backend::Pclmul].into_iter().filter(|b| b.is_available()).collect();
+    println!("Backends available : {:?}, detected : {:?}", backends, Backend::detect());
+    let mut rng = StdRng::seed_from_u64(128);
+    let mut check = true;
+    let edges = [0u128, 1, 2, 0x87, 1 << 63, 1 << 64, 1 << 127, u64::MAX as u128, u128::MAX, u128::MAX << 64, u128::MAX - 1];
+    let mut pairs: Vec<(u128, u128)> = edges.iter().flat_map(|&a| edges.iter().map(move |&b| (a, b))).collect();
+    for _ in 0..10000 {pairs.push((rng.random::<u128>(), rng.random::<u128>()))}
+    for &(a, b) in &pairs {
+        let expected = reference_mul(a, b);
+        for backend in &backends {check &= backend.mul(a, b) == expected}
+        check &= GF128::from(a).multiply(&GF128::from(b)).to_u128() == expected;
+    }
+    println!("Products of {} pairs, Check result correctness :{}", pairs.len(), check);
+    let mut check = true;
+    for len in 1..=64 {
+        let row: Vec<GF128> = (0..len).map(|_| GF128::from(rng.random::<u128>())).collect();
+        let values: Vec<GF128> = (0..len).map(|_| GF128::from(rng.random::<u128>())).collect();
+        let expected = row.iter().zip(&values).fold(0u128, |acc, (m, v)| acc ^ reference_mul(m.to_u128(), v.to_u128()));
+        for backend in &backends {check &= backend.dot(&row, &values).to_u128() == expected}
+    }
+    println!("Dot products of 1 to 64 elements, Check result correctness :{}", check);
+    let mut check = GF128::from(0).invert().is_zero();
+    for _ in 0..200 {
+        let a = GF128::from(rng.random::<u128>() | 1);
+        check &= reference_mul(a.to_u128(), a.invert().to_u128()) == 1;
+    }
+    println!("Inverses of 200 elements, Check result correctness :{}", check);
+    for backend in &backends {
+        let (mut x, y) = (rng.random::<u128>(), rng.random::<u128>() | 1);
+        let start = Instant::now();
+        for _ in 0..1_000_000 {x = backend.mul(x, y)}
+        println!("1M products with the {:?} backend in {:?} ({:x})", backend, start.elapsed(), x as u8);
+    }
+}
+
+// Constant-time inversion, pow_ct and the pivoting of GF128Matrix::invert : inverses against a*a^-1 = 1, pow_ct
+// against pow and Fermat, matrix inverses of matrices needing row exchanges. Times the inversion of 0, 1 and random
+// elements, which must take about the same time
+pub fn gf_constant_time_check(){
+    println!("{}", "-".repeat(100));
+    let mut rng = StdRng::seed_from_u64(22);
+    let mut check = GF128::from(0).invert().is_zero() && GF128::from(1).invert().is_one();
+    for _ in 0..1000 {
+        let a = GF128::from(rng.random::<u128>());
+        check &= a.is_zero() || reference_mul(a.to_u128(), a.invert().to_u128()) == 1;
+        check &= a.invert().to_u128() == a.pow_ct(u128::MAX - 1).to_u128();
+    }
+    println!("Constant-time inverses of 1000 elements, Check result correctness :{}", check);
+    let mut check = GF128::from(0).pow_ct(0).is_one() && GF128::from(0).pow_ct(5).is_zero();
+    for _ in 0..1000 {
+        let a = GF128::from(rng.random::<u128>() | 1);
+        let exponent = rng.random::<u16>() as usize;
+        check &= a.pow_ct(exponent as u128).to_u128() == a.pow(exponent).to_u128();
+        check &= a.pow_ct(u128::MAX).is_one();
+    }
+    println!("Constant-time powers of 1000 elements, Check result correctness :{}", check);
+    let mut check = true;
+    for n in [2usize, 3, 8, 16, 40] {
+        // Zero diagonal : every pivot needs rows from below
+        let mut matrix = GF128Matrix::random(n);
+        for i in 0..n {matrix.data[i][i] = GF128::from(0)}
+        if let Some(inverse) = matrix.invert() {check &= matrix.multiply(&inverse).matrices_equal(&GF128Matrix::identity(n))}
+        else {check = false}
+        // Two equal rows
+        matrix.data[n - 1] = matrix.data[0].clone();
+        check &= matrix.invert().is_none();
+    }
+    check &= GF128Matrix::new(4).invert().is_none();
+    println!("Matrix inverses with row exchanges, Check result correctness :{}", check);
+    // Two equal nodes : the zero Schur complement is only reported once the bordering is done, and none of the
+    // inverses computed meanwhile is kept
+    let mut check = true;
+    for n in [4usize, 20, 40] {
+        let mut nodes = GF128Vector::random(n);
+        nodes.elements[n - 2] = nodes.elements[1];
+        let matrix = GF128Matrix::vandermonde(&nodes);
+        let mut inverses = VandermondeInverses::default();
+        check &= inverses.require(&matrix, &[n]) == Err(RsbeError::MatrixNotInvertible) && inverses.max_size() == 0;
+        check &= inverses.require(&matrix, &[n - 2]).is_ok() && inverses.get(n - 1).is_none();
+        check &= matrix.invert_vandermonde(n).is_err() && matrix.invert_vandermonde_both(n).is_err() && matrix.invert_vandermonde(n - 2).is_ok();
+    }
+    println!("Singular Vandermonde prefixes are rejected, Check result correctness :{}", check);
+    for (name, value) in [("zero", 0u128), ("one", 1), ("random", rng.random::<u128>())] {
+        let start = Instant::now();
+        for _ in 0..10_000 {std::hint::black_box(GF128::from(std::hint::black_box(value)).invert());}
+        println!("10K inversions of {} in {:?}", name, start.elapsed());
+    }
+}
+
+// GF128::batch_invert against invert on slices of 0 to 64 elements, zeros included, then timing of both on the
+// 2t - 1 inverted betas of a key scheme and of the key scheme generation itself
+pub fn batch_invert_check(){
+    println!("{}", "-".repeat(100));
+    let mut rng = StdRng::seed_from_u64(23);
+    let mut check = true;
+    for len in 0..=64 {
+        let mut elements: Vec<GF128> = (0..len).map(|_| GF128::from(rng.random::<u128>())).collect();
+        for _ in 0..len / 8 {elements[rng.random_range(0..len)] = GF128::from(0)}
+        let expected: Vec<u128> = elements.iter().map(|e| e.invert().to_u128()).collect();
+        GF128::batch_invert(&mut elements);
+        check &= elements.iter().map(|e| e.to_u128()).eq(expected);
+    }
+    println!("Batch inverses of 0 to 64 elements, Check result correctness :{}", check);
+    let prp = common_ciphers::CommonCipher::newcipher(&AES128, &[rng.random::<u128>(), rng.random::<u128>()]);
+    for t in [4usize, 8, 16, 30] {
+        let elements: Vec<GF128> = (0..2 * t - 1).map(|_| GF128::from(rng.random::<u128>())).collect();
+        let start = Instant::now();
+        for _ in 0..1000 {std::hint::black_box(elements.iter().map(|e| e.invert()).collect::<Vec<GF128>>());}
+        let separate = start.elapsed();
+        let start = Instant::now();
+        for _ in 0..1000 {  let mut batch = elements.clone();
+                            GF128::batch_invert(&mut batch);
+                            std::hint::black_box(batch);
+                        }
+        let batched = start.elapsed();
+        let (key, iv) = (GF128::random(), GF128::random());
+        let start = Instant::now();
+        for _ in 0..1000 {std::hint::black_box(generate_key_scheme(&[key], &iv, t, &prp).is_ok());}
+        println!("t = {:>2} : 1000 x {} inversions separately {:?}, batched {:?}, 1000 key schemes {:?}", t, 2 * t - 1, separate, batched, start.elapsed());
+    }
+}
+
+// GF128Poly : Karatsuba against schoolbook, a = q*b + r, gcd of polynomials with a known common factor,
+// multi-point evaluation against Horner and interpolation roundtrips. Then the Shamir encoding of a level is
+// cross-checked against the explicit evaluation of its polynomials : with a single level, block i * max_part_size
+// + index (i > 0) of the ciphertext is P(x_i) + alpha, P having the coefficients (m_j + alpha) * beta_j
+pub fn poly_check(){
+    println!("{}", "-".repeat(100));
+    let mut rng = StdRng::seed_from_u64(24);
+    let mut check = GF128Poly::zero().multiply(&GF128Poly::random(10)).is_zero();
+    for _ in 0..200 {
+        let (a, b) = (GF128Poly::random(rng.random_range(0..200)), GF128Poly::random(rng.random_range(0..200)));
+        let product = a.multiply_schoolbook(&b);
+        check &= a.multiply_karatsuba(&b) == product && &a * &b == product && product.degree() == Some(a.degree().unwrap() + b.degree().unwrap());
+        let x = GF128::random();
+        check &= product.eval(&x) == a.eval(&x) * b.eval(&x) && (&a + &b).eval(&x) == a.eval(&x) + b.eval(&x);
+    }
+    println!("Karatsuba and schoolbook products, Check result correctness :{}", check);
+    let mut check = GF128Poly::random(5).divrem(&GF128Poly::zero()) == Err(RsbeError::DivisionByZero);
+    for _ in 0..200 {
+        let (a, b) = (GF128Poly::random(rng.random_range(0..100)), GF128Poly::random(rng.random_range(0..60)));
+        let (q, r) = a.divrem(&b).unwrap();
+        check &= &(&q * &b) + &r == a && r.degree().is_none_or(|d| d < b.degree().unwrap());
+    }
+    for _ in 0..50 {
+        let c = GF128Poly::random(rng.random_range(1..20));
+        let (a, b) = (GF128Poly::random(rng.random_range(0..30)), GF128Poly::random(rng.random_range(0..30)));
+        let g = (&a * &c).gcd(&(&b * &c)).unwrap();
+        check &= g == c.scale(&c.leading_coefficient().unwrap().invert()) && g.leading_coefficient().unwrap().is_one();
+    }
+    println!("Divisions with remainder and GCD, Check result correctness :{}", check);
+    let mut check = true;
+    for n in [1usize, 2, 15, 16, 17, 100, 300] {
+        let poly = GF128Poly::random(n + 7);
+        let points: Vec<GF128> = (0..n).map(|_| GF128::random()).collect();
+        check &= poly.evaluate_many(&points).iter().zip(&points).all(|(y, x)| *y == poly.eval(x));
+        let values: Vec<GF128> = (0..n).map(|_| GF128::random()).collect();
+        let interpolated = GF128Poly::interpolate(&points, &values).unwrap();
+        check &= interpolated.degree().is_none_or(|d| d < n) && interpolated.evaluate_many(&points) == values;
+        let low = GF128Poly::random(n - 1);
+        check &= GF128Poly::interpolate(&points, &low.evaluate_many(&points)).unwrap() == low;
+    }
+    let points = [GF128::from(3), GF128::from(5), GF128::from(3)];
+    check &= GF128Poly::interpolate(&points, &points) == Err(RsbeError::DuplicatePoints);
+    println!("Multi-point evaluation and Lagrange interpolation, Check result correctness :{}", check);
+    for size in [KARATSUBA_THRESHOLD, 256, 1024] {
+        let (a, b) = (GF128Poly::random(size - 1), GF128Poly::random(size - 1));
+        let start = Instant::now();
+        let schoolbook = a.multiply_schoolbook(&b);
+        let schoolbook_time = start.elapsed();
+        let start = Instant::now();
+        let karatsuba = a.multiply_karatsuba(&b);
+        println!("Product of two polynomials of {} coefficients : schoolbook {:?}, Karatsuba {:?}, Check result correctness :{}",
+                 size, schoolbook_time, start.elapsed(), schoolbook == karatsuba);
+    }
+    // Encoding cross-check
+    let (key, iv) = (GF128::random(), GF128::random());
+    let prp = common_ciphers::CommonCipher::newcipher(&AES128, &[key.to_u128(), key.to_u128()]);
+    for (t, blocks_count) in [(3usize, 50usize), (8, 1000), (16, 5000)] {
+        let plaintext: Vec<GF128> = (0..blocks_count).map(|_| GF128::random()).collect();
+        let mut blocks = plaintext.clone();
+        let mut st = SSCipherCore::in_place(&mut blocks, t, AES128, &[key], &iv).unwrap();
+        st.set_max_depth(Some(1)).unwrap();
+        st.encrypt().unwrap();
+        drop(st);
+        let key_scheme = generate_key_scheme(&[key], &iv, t, &prp).unwrap();
+        let level = LevelParams::top(blocks_count, t).unwrap();
+        let points: Vec<GF128> = (0..level.threshold).map(|i| key_scheme.vender_matrix.data[i][1]).collect();
+        let mut check = true;
+        for index in 0..level.max_part_size {
+            let threshold = if index < level.last_part_size {level.threshold} else {level.threshold - 1};
+            let (alpha, beta_vector) = key_scheme.masks_at(level.threshold, index);
+            let poly = GF128Poly::new((0..threshold).map(|j| (plaintext[j * level.max_part_size + index] + alpha) * beta_vector.elements[j]).collect());
+            let shares = poly.evaluate_many(&points[..threshold]);
+            for i in 1..threshold {check &= blocks[i * level.max_part_size + index] == shares[i] + alpha}
+            check &= GF128Poly::interpolate(&points[..threshold], &shares).unwrap() == poly;
+        }
+        println!("Shamir encoding of {} vectors (t = {}) against polynomial evaluation, Check result correctness :{}", level.max_part_size, level.threshold, check);
+    }
+}
+
+// FIPS-197 known answers (appendices B, C.1 and C.3) for the table based, AES-NI and bitsliced implementations, which
+// must also agree on random keys and blocks. Times 1M block encryptions with each of them
+pub fn aes_backend_check(){
+    println!("{}", "-".repeat(100));
+    let backends: Vec<AesBackend> = [AesBackend::Table, AesBackend::AesNi].into_iter().filter(|b| b.is_available()).collect();
+    println!("AES backends available : {:?}, detected : {:?}", backends, AesBackend::detect());
+    let vectors_128 = [(0x2b7e151628aed2a6abf7158809cf4f3c_u128, 0x3243f6a8885a308d313198a2e0370734_u128, 0x3925841d02dc09fbdc118597196a0b32_u128),
+                       (0x000102030405060708090a0b0c0d0e0f, 0x00112233445566778899aabbccddeeff, 0x69c4e0d86a7b0430d8cdb78070b4c55a)];
+    let vectors_256 = [([0x000102030405060708090a0b0c0d0e0f_u128, 0x101112131415161718191a1b1c1d1e1f_u128], 0x00112233445566778899aabbccddeeff_u128,
+                        0x8ea2b7ca516745bfeafc49904b496089_u128)];
+    let mut rng = StdRng::seed_from_u64(197);
+    for &backend in &backends {
+        let mut check = true;
+        for (key, plain, cipher) in vectors_128 {
+            let aes = AES128::with_backend(&[key], backend);
+            check &= aes.backend() == backend && aes.encrypt_block(plain) == cipher && aes.decrypt_block(cipher) == plain;
+        }
+        for (key, plain, cipher) in vectors_256 {
+            let aes = AES256::with_backend(&key, backend);
+            check &= aes.backend() == backend && aes.encrypt_block(plain) == cipher && aes.decrypt_block(cipher) == plain;
+        }
+        println!("FIPS-197 vectors with the {:?} backend, Check result correctness :{}", backend, check);
+    }
+    let mut check = true;
+    for (key, plain, cipher) in vectors_128 {
+        let aes = AES128Bitsliced::new(&[key]);
+        let mut blocks = [plain; 5];
+        aes.encrypt_blocks(&mut blocks);
+        check &= aes.encrypt_block(plain) == cipher && aes.decrypt_block(cipher) == plain && blocks == [cipher; 5];
+    }
+    for (key, plain, cipher) in vectors_256 {
+        let aes = AES256Bitsliced::new(&key);
+        let mut blocks = [cipher; 5];
+        aes.decrypt_blocks(&mut blocks);
+        check &= aes.encrypt_block(plain) == cipher && aes.decrypt_block(cipher) == plain && blocks == [plain; 5];
+    }
+    println!("FIPS-197 vectors with the bitsliced AES, Check result correctness :{}", check);
+    let mut check = true;
+    for _ in 0..1000 {
+        let (key, block) = ([rng.random::<u128>(), rng.random::<u128>()], rng.random::<u128>());
+        let (table_128, table_256) = (AES128::with_backend(&key, AesBackend::Table), AES256::with_backend(&key, AesBackend::Table));
+        for &backend in &backends {
+            let (aes_128, aes_256) = (AES128::with_backend(&key, backend), AES256::with_backend(&key, backend));
+            check &= aes_128.encrypt_block(block) == table_128.encrypt_block(block) && aes_128.decrypt_block(block) == table_128.decrypt_block(block)
+                     && aes_256.encrypt_block(block) == table_256.encrypt_block(block) && aes_256.decrypt_block(block) == table_256.decrypt_block(block);
+        }
+        let (bitsliced_128, bitsliced_256) = (AES128Bitsliced::new(&key), AES256Bitsliced::new(&key));
+        let blocks: Vec<u128> = (0..rng.random_range(1..10)).map(|_| rng.random::<u128>()).collect();
+        for (bitsliced, table) in [(common_ciphers::CommonCipher::AES128Bitsliced(bitsliced_128), common_ciphers::CommonCipher::AES128(table_128)),
+                                   (common_ciphers::CommonCipher::AES256Bitsliced(bitsliced_256), common_ciphers::CommonCipher::AES256(table_256))] {
+            let (mut encrypted, mut expected) = (blocks.clone(), blocks.clone());
+            bitsliced.encrypt_blocks(&mut encrypted);
+            table.encrypt_blocks(&mut expected);
+            check &= encrypted == expected;
+            bitsliced.decrypt_blocks(&mut encrypted);
+            check &= encrypted == blocks;
+        }
+    }
+    println!("Backends and bitsliced AES agree on 1000 random keys and blocks, Check result correctness :{}", check);
+    for &backend in &backends {
+        let aes = AES128::with_backend(&[rng.random::<u128>()], backend);
+        let mut block = rng.random::<u128>();
+        let start = Instant::now();
+        for _ in 0..1_000_000 {block = aes.encrypt_block(block)}
+        println!("1M AES-128 blocks with the {:?} backend in {:?} ({:x})", backend, start.elapsed(), block as u8);
+    }
+    let aes = AES128Bitsliced::new(&[rng.random::<u128>()]);
+    let mut blocks = [rng.random::<u128>(); 4];
+    let start = Instant::now();
+    for _ in 0..250_000 {aes.encrypt_blocks(&mut blocks)}
+    println!("1M AES-128 blocks with the bitsliced AES (4 per call) in {:?} ({:x})", start.elapsed(), blocks[0] as u8);
+}
@@ -0,0 +1,117 @@
+use zeroize::Zeroizing;
+use crate::{cipher::kdf::kdf_counter, common_ciphers::{CipherName, CommonCipher}, error::RsbeError, GF128};
+
+// Authenticated RSBE mode : GHASH-style polynomial MAC over GF(2^128) computed on the associated data and the
+// ciphertext, then encrypted with a PRP keyed apart from the one of the scheme
+//
+//  PRP_mac = PRP keyed with kdf_counter(PRP, MAC_LABEL, "")
+//  H       = PRP_mac(0)
+//  S       = A_1*H^(m+n+1) + ... + A_m*H^(n+2) + C_1*H^(n+1) + ... + C_n*H^2 + L*H
+//  tag     = PRP_mac(S + IV)
+//
+// A and C are zero padded to whole blocks, L holds the bit lengths of A and C (64 bits each). Since RSBE is
+// linear apart from its terminal PRP block, the tag must be checked before any plaintext is released. The RSBE chain
+// starts with PRP(x0 + IV) : keyed with the same PRP, the tag PRP(S + IV) would be an output of the same permutation
+// on a related input.
+
+pub const TAG_SIZE: usize = 16;
+pub const MAC_LABEL: &[u8] = b"RSBE aead";
+
+// PRP_mac of the scheme PRP `prp`, a `prp_name` cipher
+pub fn mac_cipher(prp: &CommonCipher, prp_name: CipherName) -> Result<CommonCipher, RsbeError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    kdf_counter(prp, MAC_LABEL, &[], &mut key[..])?;
+    let key = Zeroizing::new([u128::from_be_bytes(key[..16].try_into().unwrap()), u128::from_be_bytes(key[16..].try_into().unwrap())]);
+    Ok(CommonCipher::newcipher(&prp_name, &key[..]))
+}
+
+// Incremental polynomial hash, associated data first then ciphertext
+pub struct PolyMac {
+    h: GF128,
+    acc: GF128,
+    ad_length: u64,
+    ct_length: u64,
+    pending: [u8; 16],
+    pending_size: usize,
+    in_ciphertext: bool,
+}
+
+impl PolyMac {
+    // `prp` is PRP_mac, see mac_cipher
+    pub fn new(prp: &CommonCipher) -> Self {
+        PolyMac { h: GF128::from(0).prp_encrypt(prp), acc: GF128::from(0), ad_length: 0, ct_length: 0,
+                  pending: [0; 16], pending_size: 0, in_ciphertext: false }
+    }
+
+    #[inline(always)]
+    fn absorb_block(&mut self, block: &[u8; 16]) {
+        self.acc = (self.acc + GF128::from(block)).multiply(&self.h);
+    }
+
+    fn absorb(&mut self, mut data: &[u8]) {
+        if self.pending_size > 0 {
+            let take = (16 - self.pending_size).min(data.len());
+            self.pending[self.pending_size..self.pending_size + take].copy_from_slice(&data[..take]);
+            self.pending_size += take;
+            data = &data[take..];
+            if self.pending_size < 16 {return}
+            let block = self.pending;
+            self.absorb_block(&block);
+            self.pending_size = 0;
+        }
+        let mut chunks = data.chunks_exact(16);
+        for chunk in &mut chunks {self.absorb_block(chunk.try_into().unwrap())}
+        let rest = chunks.remainder();
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.pending_size = rest.len();
+    }
+
+    // Zero pad the pending partial block, so that associated data and ciphertext start on block boundaries
+    fn flush(&mut self) {
+        if self.pending_size > 0 {
+            self.pending[self.pending_size..].fill(0);
+            let block = self.pending;
+            self.absorb_block(&block);
+            self.pending_size = 0;
+        }
+    }
+
+    // Associated data, must be given before any ciphertext
+    pub fn update_associated_data(&mut self, data: &[u8]) {
+        debug_assert!(!self.in_ciphertext);
+        self.ad_length += data.len() as u64;
+        self.absorb(data);
+    }
+
+    pub fn update_ciphertext(&mut self, data: &[u8]) {
+        if !self.in_ciphertext {self.flush(); self.in_ciphertext = true;}
+        self.ct_length += data.len() as u64;
+        self.absorb(data);
+    }
+
+    pub fn finalize(mut self, iv: &GF128, prp: &CommonCipher) -> [u8; TAG_SIZE] {
+        self.flush();
+        let mut lengths = [0u8; 16];
+        lengths[..8].copy_from_slice(&(self.ad_length * 8).to_be_bytes());
+        lengths[8..].copy_from_slice(&(self.ct_length * 8).to_be_bytes());
+        self.absorb_block(&lengths);
+        (self.acc + *iv).prp_encrypt(prp).to_bytes()
+    }
+}
+
+// One shot tag computation from the scheme PRP
+pub fn compute_tag(prp: &CommonCipher, prp_name: CipherName, iv: &GF128, associated_data: &[&[u8]], ciphertext: &[u8]) -> Result<[u8; TAG_SIZE], RsbeError> {
+    let mac_prp = mac_cipher(prp, prp_name)?;
+    let mut mac = PolyMac::new(&mac_prp);
+    for data in associated_data {mac.update_associated_data(data)}
+    mac.update_ciphertext(ciphertext);
+    Ok(mac.finalize(iv, &mac_prp))
+}
+
+// Tag comparison in constant time with respect to the position of the first difference
+pub fn verify_tag(expected: &[u8; TAG_SIZE], found: &[u8]) -> bool {
+    if found.len() != TAG_SIZE {return false}
+    let mut diff = 0u8;
+    for i in 0..TAG_SIZE {diff |= expected[i] ^ found[i]}
+    diff == 0
+}
@@ -1,6 +1,7 @@
-use std::ptr;
+use std::{fmt, ptr};
+use zeroize::Zeroizing;
 use rand::Rng;
-use crate::common_ciphers::{CipherName,  CommonCipher};
+use crate::{cipher::padding::padding_size, common_ciphers::{CipherName,  CommonCipher}, error::RsbeError};
 
 pub struct CBCCipherCore <'a>{
     pub internal: &'a mut Vec<u128>,
@@ -8,9 +9,19 @@ pub struct CBCCipherCore <'a>{
     pub prp_name :CipherName,
     pub prp :CommonCipher, 
     iv :u128,
-    key :[u128;2]
+    key :Zeroizing<[u128;2]>,                // Wiped on drop
+    pub padded :bool,
+    out_length :usize
     }
 
+// Key and IV are left out
+impl fmt::Debug for CBCCipherCore<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CBCCipherCore").field("prp_name", &self.prp_name).field("blocks_count", &self.blocks_count)
+                            .field("padded", &self.padded).finish_non_exhaustive()
+    }
+}
+
 impl <'a>CBCCipherCore<'a>{    
     pub fn new(bytes: &[u8], in_length :usize,add_padd :bool, out_bytes :&'a mut Vec<u128>, prp_name :CipherName) -> Self {                
         let length = if in_length==0 {bytes.len()} else {in_length};
@@ -39,52 +50,60 @@ impl <'a>CBCCipherCore<'a>{
         let key2 = rand::rng().random::<u128>();
         let iv = rand::rng().random::<u128>();
         let prp = CommonCipher::newcipher(&prp_name,&[key1,key2]);        
-        CBCCipherCore {  internal: out_bytes, blocks_count, prp , prp_name, iv, key: [key1,key2] }
+        CBCCipherCore {  internal: out_bytes, blocks_count, prp , prp_name, iv, key: Zeroizing::new([key1,key2]), 
+                        padded :add_padd, out_length :blocks_count * 16 }
     }
     
     pub fn get_bytes_out(&self) -> &[u8] {
         unsafe {    std::slice::from_raw_parts(
                     self.internal.as_ptr() as *const u8,
-                    self.internal.len() * 16
+                    self.out_length
                     )
                 }
     }
 
     pub fn set_key_materials(&mut self, key :&[u128], iv:u128, prp_name :CipherName){
         self.prp = CommonCipher::newcipher(&prp_name, key);
-        self.key = [key[0],key[1]];
+        self.key = Zeroizing::new([key[0],key[1]]);
         self.iv =iv;
         self.prp_name =prp_name;
     }
 
-    pub fn get_block(&self, index: usize) -> u128 {
-        if index < self.internal.len() { self.internal[index]}            
-        else {panic!("Index outside the size of data.")}
+    pub fn get_block(&self, index: usize) -> Result<u128, RsbeError> {
+        if index < self.internal.len() { Ok(self.internal[index])}            
+        else {Err(RsbeError::IndexOutOfRange)}
     }
 
-    pub fn set_block(&mut self, index: usize, value :u128) {
-        if index < self.internal.len() {self.internal[index] = value} 
-        else {panic!("Index outside the size of data.")}
+    pub fn set_block(&mut self, index: usize, value :u128) -> Result<(), RsbeError> {
+        if index < self.internal.len() {self.internal[index] = value; Ok(())} 
+        else {Err(RsbeError::IndexOutOfRange)}
     }
     
     pub fn blocks(&self) -> impl Iterator<Item = u128 > {        
-        (0..self.blocks_count).filter_map(move |i| Some(self.get_block(i)))
+        (0..self.blocks_count).filter_map(move |i| self.get_block(i).ok())
     }
 
            
     pub fn encrypt(&mut self) {
             let previous_block = self.iv;
             for i in 0..self.blocks_count{
-                    let encrypted_block = self.prp.encrypt_block(self.get_block(i) ^ previous_block);
-                    self.set_block(i, encrypted_block);
+                    let encrypted_block = self.prp.encrypt_block(self.internal[i] ^ previous_block);
+                    self.internal[i] = encrypted_block;
                 }            
+            self.out_length = self.blocks_count * 16;
     }
 
-   pub fn decrypt(&mut self) {
+   pub fn decrypt(&mut self) -> Result<(), RsbeError> {
             let previous_block = self.iv;
             for i in 0..self.blocks_count{
-                    let decrypted_block = self.prp.decrypt_block(self.get_block(i))^ previous_block;
-                    self.set_block(i, decrypted_block);
+                    let decrypted_block = self.prp.decrypt_block(self.internal[i])^ previous_block;
+                    self.internal[i] = decrypted_block;
                 }            
+            self.out_length = self.blocks_count * 16;
+            // Remove padding
+            if self.padded {    let last_block = self.internal.last().ok_or(RsbeError::InvalidLength)?;
+                                self.out_length -= padding_size(&last_block.to_le_bytes())?;
+                            }
+            Ok(())
     }
 }
\ No newline at end of file
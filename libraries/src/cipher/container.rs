@@ -0,0 +1,155 @@
+use crate::{cipher::{aead::TAG_SIZE, core::{MaskSchedule, SSCipherCore, MAX_DEPTH, MAX_THRESHOLD, MIN_THRESHOLD}}, common_ciphers::CipherName, error::RsbeError, GF128};
+
+// Self-describing RSBE ciphertext container. All integers are big-endian.
+//
+//  offset  size  field
+//  0       4     magic "RSBE"
+//  4       1     format version (currently 1)
+//  5       1     PRP identifier (CipherName::id)
+//  6       1     flags (bit 0 : a key id follows the fixed part, bit 1 : authenticated, a tag follows the ciphertext,
+//                bit 2 : the recursion depth is capped)
+//  7       1     mask schedule (MaskSchedule::id, 0 for the containers written before it was recorded)
+//  8       2     targeted threshold
+//  10      16    IV
+//  26      8     plaintext length in bytes
+//  34      1     optional maximal recursion depth (1..=255), only when flag bit 2 is set
+//  ...     1+n   optional key id : length n (1..=255) followed by n bytes
+//  ...           ciphertext blocks : (plaintext length / 16 + 1) blocks of 16 bytes (padding included)
+//  ...     16    authentication tag, only when flag bit 1 is set (see aead.rs)
+//
+// A reader must reject unknown versions and unknown flags rather than guess, new fields go into a new version.
+
+pub const MAGIC: [u8; 4] = *b"RSBE";
+pub const FORMAT_VERSION: u8 = 1;
+const FIXED_HEADER_SIZE: usize = 34;
+const FLAG_KEY_ID: u8 = 0x01;
+const FLAG_AUTHENTICATED: u8 = 0x02;
+const FLAG_MAX_DEPTH: u8 = 0x04;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RsbeHeader {
+    pub version: u8,
+    pub prp_name: CipherName,
+    pub targted_threshold: usize,
+    pub iv: GF128,
+    pub plaintext_length: u64,
+    pub key_id: Option<Vec<u8>>,
+    pub authenticated: bool,
+    pub mask_schedule: MaskSchedule,
+    pub max_depth: Option<usize>,
+}
+
+impl RsbeHeader {
+    pub fn new(prp_name: CipherName, targted_threshold: usize, iv: GF128, plaintext_length: u64, key_id: Option<Vec<u8>>) -> Self {
+        RsbeHeader { version: FORMAT_VERSION, prp_name, targted_threshold, iv, plaintext_length, key_id, authenticated: false,
+                      mask_schedule: MaskSchedule::Generator, max_depth: None }
+    }
+
+    // Size in bytes of the encoded header
+    pub fn size(&self) -> usize {
+        FIXED_HEADER_SIZE + self.max_depth.map_or(0, |_| 1) + self.key_id.as_ref().map_or(0, |id| 1 + id.len())
+    }
+
+    // Number of ciphertext bytes following the header (the last block holds the padding). The plaintext length comes
+    // from an untrusted header : InvalidHeader when the size does not fit in a usize
+    pub fn ciphertext_size(&self) -> Result<usize, RsbeError> {
+        let plaintext_length = usize::try_from(self.plaintext_length).map_err(|_| RsbeError::InvalidHeader)?;
+        (plaintext_length / 16).checked_add(1).and_then(|blocks| blocks.checked_mul(16)).ok_or(RsbeError::InvalidHeader)
+    }
+
+    // Number of bytes following the ciphertext blocks
+    pub fn tag_size(&self) -> usize {
+        if self.authenticated {TAG_SIZE} else {0}
+    }
+
+    pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), RsbeError> {
+        let threshold = u16::try_from(self.targted_threshold).map_err(|_| RsbeError::InvalidHeader)?;
+        if self.targted_threshold < MIN_THRESHOLD {return Err(RsbeError::ThresholdTooSmall)}
+        if self.targted_threshold > MAX_THRESHOLD {return Err(RsbeError::ThresholdTooLarge)}
+        let flags = (if self.key_id.is_some() {FLAG_KEY_ID} else {0}) | (if self.authenticated {FLAG_AUTHENTICATED} else {0})
+                    | (if self.max_depth.is_some() {FLAG_MAX_DEPTH} else {0});
+        out.extend_from_slice(&MAGIC);
+        out.push(FORMAT_VERSION);
+        out.push(self.prp_name.id());
+        out.push(flags);
+        out.push(self.mask_schedule.id());
+        out.extend_from_slice(&threshold.to_be_bytes());
+        out.extend_from_slice(&self.iv.to_bytes());
+        out.extend_from_slice(&self.plaintext_length.to_be_bytes());
+        if let Some(depth) = self.max_depth {
+            if depth == 0 || depth > MAX_DEPTH {return Err(RsbeError::InvalidDepth)}
+            out.push(depth as u8);
+        }
+        if let Some(id) = &self.key_id {
+            if id.is_empty() || id.len() > 255 {return Err(RsbeError::InvalidHeader)}
+            out.push(id.len() as u8);
+            out.extend_from_slice(id);
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RsbeError> {
+        let mut out = Vec::with_capacity(self.size());
+        self.write_to(&mut out)?;
+        Ok(out)
+    }
+
+    // Parse a header from the start of `bytes`, returns the header and its encoded size
+    pub fn parse(bytes: &[u8]) -> Result<(Self, usize), RsbeError> {
+        if bytes.len() < FIXED_HEADER_SIZE || bytes[0..4] != MAGIC {return Err(RsbeError::InvalidHeader)}
+        let version = bytes[4];
+        if version != FORMAT_VERSION {return Err(RsbeError::UnsupportedVersion(version))}
+        let prp_name = CipherName::from_id(bytes[5]).ok_or(RsbeError::UnknownCipher(bytes[5]))?;
+        let flags = bytes[6];
+        if flags & !(FLAG_KEY_ID | FLAG_AUTHENTICATED | FLAG_MAX_DEPTH) != 0 {return Err(RsbeError::InvalidHeader)}
+        let mask_schedule = MaskSchedule::from_id(bytes[7]).ok_or(RsbeError::InvalidHeader)?;
+        let targted_threshold = u16::from_be_bytes([bytes[8], bytes[9]]) as usize;
+        if targted_threshold < MIN_THRESHOLD {return Err(RsbeError::ThresholdTooSmall)}
+        if targted_threshold > MAX_THRESHOLD {return Err(RsbeError::ThresholdTooLarge)}
+        let iv = GF128::from(<&[u8; 16]>::try_from(&bytes[10..26]).unwrap());
+        let plaintext_length = u64::from_be_bytes(bytes[26..34].try_into().unwrap());
+        let mut size = FIXED_HEADER_SIZE;
+        let max_depth = if flags & FLAG_MAX_DEPTH != 0 {
+            let depth = *bytes.get(size).ok_or(RsbeError::InvalidHeader)? as usize;
+            if depth == 0 {return Err(RsbeError::InvalidHeader)}
+            size += 1;
+            Some(depth)
+        } else {None};
+        let key_id = if flags & FLAG_KEY_ID != 0 {
+            let id_len = *bytes.get(size).ok_or(RsbeError::InvalidHeader)? as usize;
+            if id_len == 0 || bytes.len() < size + 1 + id_len {return Err(RsbeError::InvalidHeader)}
+            let id = bytes[size + 1..size + 1 + id_len].to_vec();
+            size += 1 + id_len;
+            Some(id)
+        } else {None};
+        let authenticated = flags & FLAG_AUTHENTICATED != 0;
+        let header = RsbeHeader { version, prp_name, targted_threshold, iv, plaintext_length, key_id, authenticated, mask_schedule, max_depth };
+        header.ciphertext_size()?;
+        Ok((header, size))
+    }
+
+    // Decryption parameters recorded in the header, applied to a core built over the ciphertext blocks
+    pub fn configure(&self, core: &mut SSCipherCore) -> Result<(), RsbeError> {
+        core.padded = true;
+        core.set_mask_schedule(self.mask_schedule);
+        core.set_max_depth(self.max_depth)
+    }
+}
+
+// Parse the container header and split off its ciphertext blocks, without the tag of an authenticated container
+pub fn container_body(container: &[u8]) -> Result<(RsbeHeader, &[u8]), RsbeError> {
+    let (header, header_size) = RsbeHeader::parse(container)?;
+    let body = &container[header_size..];
+    let ciphertext_size = header.ciphertext_size()?;
+    if ciphertext_size.checked_add(header.tag_size()) != Some(body.len()) {return Err(RsbeError::InvalidLength)}
+    Ok((header, &body[..ciphertext_size]))
+}
+
+// Parse the container header and rebuild the SSCipherCore holding its ciphertext blocks, ready to be decrypted
+// with the parameters recorded in the header. The tag of an authenticated container is not checked here
+pub fn open_container<'a>(container: &[u8], out_bytes: &'a mut Vec<GF128>, key: &[GF128]) -> Result<(RsbeHeader, SSCipherCore<'a>), RsbeError> {
+    let (header, body) = container_body(container)?;
+    let mut core = SSCipherCore::with_key(body, body.len(), false, out_bytes, header.targted_threshold, header.prp_name, key, &header.iv)?;
+    header.configure(&mut core)?;
+    Ok((header, core))
+}
@@ -1,12 +1,23 @@
-use std::{arch::x86_64::*, ptr};
+use std::{fmt, ptr, sync::Mutex};
+use zeroize::{Zeroize, Zeroizing};
 use smallvec::SmallVec;
-use crate::{common_ciphers::{ CipherName, CommonCipher}, galois_arithmetic::{field::MAX_VECTOR_ELEMENTS, vector::GF128Vector, GF128Matrix}, GF128};
+use rayon::{iter::{IntoParallelIterator, ParallelIterator}, ThreadPool, ThreadPoolBuilder};
+use crate::{cipher::{aead::{compute_tag, verify_tag}, kdf::{passphrase_kdf, PassphraseParams}, container::{container_body, RsbeHeader}, padding::padding_size, plan::{level_plan_with, LevelPlan}}, common_ciphers::{ CipherName, CommonCipher}, error::RsbeError, galois_arithmetic::{backend, field::MAX_VECTOR_ELEMENTS, vector::GF128Vector, GF128Matrix, VandermondeInverses, VandermondeSolver}, GF128};
+
+// With a targeted threshold of 2, the incomplete vectors of a level hold a single block, which the encoding leaves
+// unchanged (first Vandermonde column and first beta power are both 1) : 3 is the smallest safe value
+pub const MIN_THRESHOLD :usize = 3;
+
+// SSCipherCore takes any threshold, vectors above the inline capacity going to the heap. A key scheme holds
+// (2t-1)^2 blocks (64 MiB at this bound) : Rsbe and the container and stream readers, which take the threshold from
+// untrusted headers, stop here
+pub const MAX_THRESHOLD :usize = 1024;
 
 // Find optimal threshold with respect to the data size and the targted threshold 
-pub fn fit_thresholds(blocks_count :usize,min_desired_threshold :usize)-> (usize,usize)
+pub fn fit_thresholds(blocks_count :usize,min_desired_threshold :usize)-> Result<(usize,usize), RsbeError>
     {   
-        if min_desired_threshold<2  {panic!("Minimal allowed value for the desired threshold  is 2")}
-        if blocks_count<min_desired_threshold {(blocks_count,1)}
+        if min_desired_threshold < MIN_THRESHOLD {return Err(RsbeError::ThresholdTooSmall)}
+        if blocks_count<min_desired_threshold {Ok((blocks_count,1))}
         else {  let a = blocks_count / (min_desired_threshold - 1);
                 let b = blocks_count % (min_desired_threshold - 1);
                 let mut result;
@@ -19,12 +30,14 @@ pub fn fit_thresholds(blocks_count :usize,min_desired_threshold :usize)-> (usize
                         else { result = (min_desired_threshold + b/a,a) ;
                                 if b % a ==0 {result.0-=1}}                        
                         }
-                result
+                Ok(result)
             }        
     }
 
- pub fn generate_key_scheme(key :&[GF128],iv :&GF128, threshold :usize, prp :&CommonCipher) -> KeyScheme
+ pub fn generate_key_scheme(key :&[GF128],iv :&GF128, threshold :usize, prp :&CommonCipher) -> Result<KeyScheme, RsbeError>
 {
+        if key.is_empty() {return Err(RsbeError::InvalidKey)}
+        if threshold < MIN_THRESHOLD {return Err(RsbeError::ThresholdTooSmall)}
         let _key;
         if key.len() ==1 {
             _key = key[0];
@@ -38,12 +51,11 @@ pub fn fit_thresholds(blocks_count :usize,min_desired_threshold :usize)-> (usize
         let beta = GF128Vector::beta_from_iv(&_key, iv, threshold,prp);
         let beta_vector = GF128Vector::beta_vector(&beta, 2*threshold-1);
         let mut inv_beta_vector = beta_vector.clone();
-        for i in 0..inv_beta_vector.true_size {
-                        inv_beta_vector.elements[i] = inv_beta_vector.elements[i].invert()
-                    };
-        let (principal_dec_matrice, secondary_dec_matrice) = vender_matrix.invert_vandermonde_both(threshold);
+        GF128::batch_invert(&mut inv_beta_vector.elements[..beta_vector.true_size]);
+        // The inverses or solvers used by the decoding are only built by decrypt (or prepare_inverses / prepare_solvers), for the levels met
+        let dec_inverses = VandermondeInverses::default();
         let _key =if key.len() == 1 {[key[0],key[0]]} else {[key[0],key[1]]};
-        KeyScheme { key  :_key,iv :*iv  , vender_matrix, principal_dec_matrice, secondary_dec_matrice, alpha, beta, beta_vector, inv_beta_vector }                    
+        Ok(KeyScheme { key  :_key,iv :*iv  , vender_matrix, dec_inverses, dec_solvers :Vec::new(), alpha, beta, beta_vector, inv_beta_vector, mask_schedule :MaskSchedule::default() })
             
         
 } 
@@ -56,49 +68,449 @@ pub struct LevelParams{
     pub blocks_count : usize,
     pub threshold: usize,
     pub max_part_size : usize,
-    pub last_part_size:usize
+    pub last_part_size:usize,
+    pub depth :usize                    // Number of levels above this one
 }
 
 pub struct KeyScheme {
     pub key : [GF128;2],
     pub iv :GF128,
     pub vender_matrix : GF128Matrix,
-    pub principal_dec_matrice : GF128Matrix,
-    pub secondary_dec_matrice :GF128Matrix,
+    pub dec_inverses : VandermondeInverses,       // Inverses of the vender_matrix prefixes, looked up by level threshold
+    pub dec_solvers : Vec<VandermondeSolver>,     // Structured solvers of the vender_matrix prefixes, one per size
     pub alpha :GF128,
     pub beta :GF128,
     pub beta_vector :GF128Vector,
-    pub inv_beta_vector :GF128Vector
+    pub inv_beta_vector :GF128Vector,
+    pub mask_schedule :MaskSchedule
+}
+
+impl KeyScheme {
+    // Encoding masks (alpha and the first `threshold` beta elements) applied to vector `index` of a level
+    pub fn masks_at(&self, threshold :usize, index :usize) -> (GF128, GF128Vector) {
+        MaskStep::encoding(self.mask_schedule).at(&self.alpha, &self.beta_vector, threshold, index)
+    }
+
+    // Structured solver of the (size x size) prefix of the vender_matrix, once prepared
+    pub fn solver(&self, size :usize) -> Option<&VandermondeSolver> {
+        self.dec_solvers.iter().find(|solver| solver.size() == size)
+    }
+
+    // Inverses or structured solvers of a level, once prepared (see SSCipherCore::prepare_levels)
+    fn level_decoder(&self, level :&LevelParams, structured :bool) -> Result<LevelDecoder<'_>, RsbeError> {
+        if structured {
+            let solver = |size :usize| self.solver(size).ok_or(RsbeError::InconsistentDimensions);
+            Ok(LevelDecoder::Solvers(solver(level.threshold)?, solver(level.threshold - 1)?))
+        }
+        else {
+            let inverse = |size :usize| self.dec_inverses.get(size).ok_or(RsbeError::InconsistentDimensions);
+            Ok(LevelDecoder::Inverses(inverse(level.threshold)?, inverse(level.threshold - 1)?))
+        }
+    }
+
+    // Make the solvers of the given sizes available
+    pub fn require_solvers(&mut self, sizes :&[usize]) -> Result<(), RsbeError> {
+        for &size in sizes {
+            if size > 0 && self.solver(size).is_none() {self.dec_solvers.push(VandermondeSolver::from_vandermonde(&self.vender_matrix, size)?)}
+        }
+        Ok(())
+    }
+}
+
+// Every field is secret or derived from the key : wiped on drop and never printed
+impl Drop for KeyScheme {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.iv.zeroize();
+        self.vender_matrix.zeroize();
+        self.dec_inverses.zeroize();
+        self.dec_solvers.iter_mut().for_each(|solver| solver.zeroize());
+        self.alpha.zeroize();
+        self.beta.zeroize();
+        self.beta_vector.zeroize();
+        self.inv_beta_vector.zeroize();
+    }
+}
+
+impl fmt::Debug for KeyScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyScheme").finish_non_exhaustive()
+    }
+}
+
+// Size of the last of the `threshold` parts, between 1 and max_part_size : the vectors of index below it hold
+// `threshold` blocks, the others `threshold - 1`. When the parts are all full it is max_part_size and not
+// blocks_count % max_part_size, which would leave the last part out of every vector
+fn last_part_size(blocks_count :usize, (threshold, max_part_size) :(usize, usize)) -> usize {
+    blocks_count - (threshold - 1) * max_part_size
 }
 
 impl LevelParams {
+    // Top decomposition level of `blocks_count` blocks
+    pub fn top(blocks_count :usize, targted_threshold :usize) -> Result<Self, RsbeError>{
+                if blocks_count == 0 {return Err(RsbeError::InvalidLength)}
+                let opt_params = fit_thresholds(blocks_count, targted_threshold)?;  // Get optimal thresholding parametres 
+                Ok(LevelParams{ start: 0, end: blocks_count-1, blocks_count, threshold: opt_params.0, 
+                                max_part_size :opt_params.1, last_part_size : last_part_size(blocks_count, opt_params), depth :0})
+            }
+
+    // Whether this level gets a Shamir encoding, otherwise it is the final level and goes through the PRP
+    pub fn is_encoded(&self, max_depth :Option<usize>) -> bool {
+                self.max_part_size > 1 && max_depth.is_none_or(|depth| self.depth < depth)
+            }
+
+    // Blocks of the final level encrypted with the PRP. When the recursion goes down to parts of a single block only
+    // block 0 is, the other blocks of the final level being covered by the levels above. Inputs of less than 2t-1
+    // blocks get no Shamir level and a level left by max_depth still holds whole parts : all their blocks are encrypted
+    pub fn terminal_blocks(&self) -> usize {
+                if self.depth > 0 && self.max_part_size == 1 {1} else {self.blocks_count}
+            }
+
     // Go down from a given decomposition level to the next according to the sharing threshold
-    pub fn go_down(&mut self, min_threshold:usize){
+    pub fn go_down(&mut self, min_threshold:usize) -> Result<(), RsbeError>{
                 self.blocks_count = self.max_part_size;
-                let new_part = fit_thresholds(self.blocks_count, min_threshold);               
+                let new_part = fit_thresholds(self.blocks_count, min_threshold)?;               
                 self.threshold = new_part.0;
                 self.max_part_size = new_part.1;                    
-                self.last_part_size = self.blocks_count % self.max_part_size; 
+                self.last_part_size = last_part_size(self.blocks_count, new_part); 
                 self.end = self.max_part_size - 1;                    
+                self.depth += 1;
+                Ok(())
+            }
+}
+
+// Vectors below this count per task are not worth splitting across threads
+const MIN_PARALLEL_CHUNK :usize = 64;
+
+// Raw pointer to the data blocks shared by the threads of a parallel level, each thread only touches its own vectors
+#[derive(Clone, Copy)]
+struct SharedBlocks(*mut GF128);
+unsafe impl Send for SharedBlocks {}
+unsafe impl Sync for SharedBlocks {}
+
+impl SharedBlocks {
+    fn get(&self) -> *mut GF128 {self.0}
+}
+
+// How alpha and the beta elements evolve from one vector to the next inside a level. Frobenius squares them (the
+// original schedule, kept as the SSCipherCore default) : squaring is the Frobenius map, so the masks repeat every 128
+// vectors. Generator multiplies them by the primitive element x, the masks then only repeat after 2^128 - 1 vectors.
+// Rsbe, the container and the stream select Generator
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MaskSchedule {
+    #[default]
+    Frobenius,
+    Generator,
+}
+
+impl MaskSchedule {
+    // Identifier recorded in the ciphertext parameters
+    pub fn id(&self) -> u8 {
+        match self {
+            MaskSchedule::Frobenius => 0,
+            MaskSchedule::Generator => 1,
+        }
+    }
+
+    pub fn from_id(id :u8) -> Option<Self> {
+        match id {
+            0 => Some(MaskSchedule::Frobenius),
+            1 => Some(MaskSchedule::Generator),
+            _ => None,
+        }
+    }
+}
+
+// Step from the masks of vector i to those of vector i+1. With the Generator schedule alpha is multiplied by x and the
+// beta elements by x, or by x^-1 for the inverted betas used by the decoding
+#[derive(Clone, Copy)]
+struct MaskStep {
+    schedule :MaskSchedule,
+    alpha_factor :GF128,
+    beta_factor :GF128,
+}
+
+impl MaskStep {
+    fn encoding(schedule :MaskSchedule) -> Self {
+        let generator = GF128::from(2);
+        MaskStep { schedule, alpha_factor: generator, beta_factor: generator }
+    }
+
+    fn decoding(schedule :MaskSchedule) -> Self {
+        let generator = GF128::from(2);
+        MaskStep { schedule, alpha_factor: generator, beta_factor: generator.invert() }
+    }
+
+    #[inline(always)]
+    fn next(&self, alpha :&mut GF128, beta_vector :&mut GF128Vector, threshold :usize) {
+        match self.schedule {
+            MaskSchedule::Frobenius => {
+                *alpha = alpha.multiply(alpha);
+                for k in 0..threshold { beta_vector.elements[k] = beta_vector.elements[k].multiply(&beta_vector.elements[k])}
+            }
+            MaskSchedule::Generator => {
+                *alpha = alpha.multiply(&self.alpha_factor);
+                for k in 0..threshold { beta_vector.elements[k] = beta_vector.elements[k].multiply(&self.beta_factor)}
+            }
+        }
+    }
+
+    // With the Generator schedule the betas of vector `index` are the initial ones times beta_factor^index : returns
+    // alpha and that common factor
+    fn scaled_at(&self, alpha :&GF128, index :usize) -> (GF128, GF128) {
+        (alpha.multiply(&self.alpha_factor.pow(index)), self.beta_factor.pow(index))
+    }
+
+    // Masks of vector `index` computed directly (x^(2^128) = x, so at most 127 squarings are needed for Frobenius)
+    fn at(&self, alpha :&GF128, beta_vector :&GF128Vector, threshold :usize, index :usize) -> (GF128, GF128Vector) {
+        let mut alpha = *alpha;
+        let mut beta_vector = beta_vector.clone();
+        match self.schedule {
+            MaskSchedule::Frobenius => for _ in 0..index % 128 {self.next(&mut alpha, &mut beta_vector, threshold)},
+            MaskSchedule::Generator => {
+                alpha = alpha.multiply(&self.alpha_factor.pow(index));
+                let beta_factor = self.beta_factor.pow(index);
+                for k in 0..threshold { beta_vector.elements[k] = beta_vector.elements[k].multiply(&beta_factor)}
+            }
+        }
+        (alpha, beta_vector)
+    }
+}
+
+// Shamir encoding of the vector `index` of the given level, done in place on the blocks array starting at `internal_slice`.
+// Safety : the blocks index + j * max_part_size (j < threshold) must lie in the array, and no other thread may access
+// them meanwhile (vectors of a same level use disjoint blocks, which is what allows the parallel path)
+#[inline(always)]    
+unsafe fn encode_vector_raw(internal_slice :*mut GF128, level :&LevelParams, vender_matrix :&GF128Matrix, index: usize, 
+                            alpha :&GF128, beta_vector :&GF128Vector, tmp_values :&mut [GF128]) {
+        let threshold = if index < level.last_part_size {level.threshold} 
+                               else {level.threshold - 1};
+        let alpha_val = *alpha;                               
+        let matrix_data = &vender_matrix.data;
+        let bv = &beta_vector.elements;
+        unsafe {    let mut j = 0;
+                    for i in 0..threshold {  tmp_values[i] = (*internal_slice.add(j + index) + alpha_val).multiply(&bv[i]);
+                                                    j += level.max_part_size;
+                                                 }
+                    for i in 0..threshold {     // Row by vector product with a single reduction
+                        let result = backend::dot(&matrix_data[i][..threshold], &tmp_values[..threshold]);
+                        *internal_slice.add(i * level.max_part_size + index) = result + alpha_val;
+                    }
+        }
+    }
+
+
+// Inverse matrices or structured solvers of sizes threshold and threshold - 1 that decode the vectors of a level
+#[derive(Clone, Copy)]
+enum LevelDecoder<'a> {
+    Inverses(&'a [GF128], &'a [GF128]),
+    Solvers(&'a VandermondeSolver, &'a VandermondeSolver),
+}
+
+// Shamir decoding of the vector `index` of the given level, in place, with the inverses of sizes threshold and
+// threshold - 1 of the level, same safety requirements as encode_vector_raw
+#[allow(clippy::too_many_arguments)]
+#[inline(always)]    
+unsafe fn decode_vector_raw(internal_slice :*mut GF128, level :&LevelParams, inverses :(&[GF128], &[GF128]),
+                            index: usize, alpha :&GF128, inv_beta_vector :&GF128Vector, tmp_values :&mut [GF128]) {
+        let (threshold, matrix_data) = if index < level.last_part_size {(level.threshold, inverses.0)} 
+                                       else {(level.threshold - 1, inverses.1)};   
+        let alpha_val = *alpha;
+        unsafe {
+            let mut j = 0;
+            for tmp in tmp_values.iter_mut().take(threshold) {  *tmp = *internal_slice.add(j + index) + alpha_val;
+                                            j += level.max_part_size;
+                                        }        
+            for i in 0..threshold {
+                let matrix_row = &matrix_data[i * threshold..(i + 1) * threshold];            
+                let result = backend::dot(matrix_row, &tmp_values[..threshold]).multiply(&inv_beta_vector.elements[i]);            
+                *internal_slice.add(i * level.max_part_size + index) = result + alpha_val;
             }
+        }
+    }
+
+// Same decoding as decode_vector_raw, the system of the vector being solved by the structured solver of its size
+// instead of multiplied by an inverse matrix. `scratch` must hold at least `threshold`
+// elements, same safety requirements as encode_vector_raw
+#[allow(clippy::too_many_arguments)]
+#[inline(always)]    
+unsafe fn decode_vector_solved_raw(internal_slice :*mut GF128, level :&LevelParams, solvers :(&VandermondeSolver, &VandermondeSolver), index: usize, 
+                                   alpha :&GF128, inv_beta_vector :&GF128Vector, tmp_values :&mut [GF128], scratch :&mut [GF128]) {
+        let (threshold, solver) = if index < level.last_part_size {(level.threshold, solvers.0)} 
+                                  else {(level.threshold - 1, solvers.1)};   
+        let alpha_val = *alpha;
+        unsafe {
+            let mut j = 0;
+            for tmp in tmp_values.iter_mut().take(threshold) {  *tmp = *internal_slice.add(j + index) + alpha_val;
+                                            j += level.max_part_size;
+                                        }        
+            solver.solve(&mut tmp_values[..threshold], scratch);
+            for (i, value) in tmp_values.iter().take(threshold).enumerate() {
+                *internal_slice.add(i * level.max_part_size + index) = value.multiply(&inv_beta_vector.elements[i]) + alpha_val;
+            }
+        }
+    }
+
+// Vandermonde matrix of a level with the initial betas folded in, row i column j holding M[i][j] * beta_j. With the
+// Generator schedule the betas of vector k are beta_j * x^k, so the encoding of vector k is x^k * (P * (v + alpha_k)) + alpha_k :
+// one product per output instead of one per input and no beta update between vectors
+struct ScaledMatrix {
+    data :Zeroizing<Vec<GF128>>,
+    threshold :usize,
+}
+
+impl ScaledMatrix {
+    fn new(matrix :&GF128Matrix, beta_vector :&GF128Vector, threshold :usize) -> Self {
+        let data = (0..threshold).flat_map(|i| (0..threshold).map(move |j| matrix.data[i][j].multiply(&beta_vector.elements[j]))).collect();
+        ScaledMatrix { data: Zeroizing::new(data), threshold }
+    }
+
+    // First `size` elements of row i (prefix used by the vectors of threshold - 1 blocks)
+    #[inline(always)]
+    fn row(&self, i :usize, size :usize) -> &[GF128] {
+        &self.data[i * self.threshold..i * self.threshold + size]
+    }
 }
 
+// Vectors encoded per pass by the interleaved kernel
+pub const INTERLEAVED_VECTORS :usize = 4;
+
+// Kernel used to encode the levels. Both give the same ciphertext : Interleaved encodes INTERLEAVED_VECTORS vectors
+// per pass over a pre-scaled matrix and only applies to the Generator schedule, the levels of the Frobenius schedule
+// always use Single (one vector at a time, the original kernel)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EncodeKernel {
+    Single,
+    #[default]
+    Interleaved,
+}
+
+// Interleaved Shamir encoding of the `count` (at most N) vectors first..first + count of the level, which must hold the
+// same number of blocks. Each row of the scaled matrix is loaded once for the N vectors, with one reduction per output.
+// `alpha` and `scale` are the masks of vector `first` and are moved to vector first + count.
+// Safety : same as encode_vector_raw for each of the vectors
+#[allow(clippy::too_many_arguments)]
+#[inline(always)]
+unsafe fn encode_vectors_raw<const N: usize>(internal_slice :*mut GF128, level :&LevelParams, scaled :&ScaledMatrix, first :usize, count :usize,
+                                              alpha :&mut GF128, scale :&mut GF128, step :&MaskStep, tmp_values :&mut [[GF128; N]]) {
+        let threshold = if first < level.last_part_size {level.threshold} 
+                               else {level.threshold - 1};
+        let mut alphas = [GF128::from(0); N];
+        let mut scales = [GF128::from(0); N];
+        for v in 0..count {     alphas[v] = *alpha;
+                                scales[v] = *scale;
+                                *alpha = alpha.multiply(&step.alpha_factor);
+                                *scale = scale.multiply(&step.beta_factor);
+                            }
+        unsafe {    for (j, tmp) in tmp_values.iter_mut().take(threshold).enumerate() {
+                        let column = j * level.max_part_size + first;
+                        for v in 0..N { tmp[v] = if v < count {*internal_slice.add(column + v) + alphas[v]} else {GF128::from(0)} }
+                    }
+                    for i in 0..threshold {
+                        let results = backend::dot_n(scaled.row(i, threshold), &tmp_values[..threshold]);
+                        let row = i * level.max_part_size + first;
+                        for v in 0..count { *internal_slice.add(row + v) = results[v].multiply(&scales[v]) + alphas[v] }
+                    }
+        }
+    }
+
+// Interleaved encoding of the vectors start..end of the level, in groups that never straddle last_part_size (the two
+// sides do not have the same number of blocks). Same safety requirements as encode_vector_raw for each of the vectors
+#[allow(clippy::too_many_arguments)]
+unsafe fn encode_range_raw(internal_slice :*mut GF128, level :&LevelParams, scaled :&ScaledMatrix, start :usize, end :usize, 
+                           alpha :&GF128, step :&MaskStep, tmp_values :&mut [[GF128; INTERLEAVED_VECTORS]]) {
+        let (mut alpha, mut scale) = step.scaled_at(alpha, start);
+        let mut first = start;
+        while first < end {
+            let limit = if first < level.last_part_size {level.last_part_size.min(end)} else {end};
+            let count = (limit - first).min(INTERLEAVED_VECTORS);
+            unsafe { encode_vectors_raw(internal_slice, level, scaled, first, count, &mut alpha, &mut scale, step, tmp_values) }
+            first += count;
+        }
+    }
+
+// DecodeSolver::Auto uses the structured solver for the levels of fewer vectors than this many times their threshold :
+// the inverses cost about t^3 products once and then save about t^2 per vector (measured break-even between 2t and 4t
+// vectors for t from 8 to 128)
+pub const STRUCTURED_SOLVER_RATIO :usize = 2;
+
+// How the levels are decoded, all give the same plaintext. Inverses multiplies each vector by the inverse of the
+// Vandermonde prefix : O(t^3) to build it once per key scheme and t^2 elements per size, then a matrix-vector product
+// per vector. Structured solves each vector with a VandermondeSolver : O(t) storage built in O(t^2), but 3 to 5 times
+// slower per vector (1.5 times the products, most of them with their own reduction). Auto takes Structured for the
+// levels with few vectors for their threshold (small messages under large thresholds), see STRUCTURED_SOLVER_RATIO
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecodeSolver {
+    Inverses,
+    Structured,
+    #[default]
+    Auto,
+}
+
+impl DecodeSolver {
+    pub fn is_structured(&self, level :&LevelParams) -> bool {
+        match self {
+            DecodeSolver::Inverses => false,
+            DecodeSolver::Structured => true,
+            DecodeSolver::Auto => level.max_part_size < STRUCTURED_SOLVER_RATIO * level.threshold,
+        }
+    }
+}
+
+// Decomposition levels kept inline by decrypt, deeper recursions spill to the heap
+const MAX_INLINE_LEVELS :usize = 32;
+
+// Largest recursion depth that can be requested (and recorded on one byte by the container)
+pub const MAX_DEPTH :usize = 255;
+
 //  Definz a structur that enables representation of a given data bytes array as a Shamir's spliting structure 
 //  that can be read as blocks, parts or vectors
 pub struct SSCipherCore<'a> {
     pub targted_threshold : usize,
-    pub internal: &'a mut Vec<GF128>, 
-    tmp_vector : SmallVec<[__m128i; MAX_VECTOR_ELEMENTS]>,
-    decryption_parts_stack : Vec<LevelParams>,
+    pub internal: &'a mut [GF128], 
+    tmp_vector : SmallVec<[GF128; MAX_VECTOR_ELEMENTS]>,
+    solver_scratch : SmallVec<[GF128; MAX_VECTOR_ELEMENTS]>,
+    decryption_parts_stack : SmallVec<[LevelParams; MAX_INLINE_LEVELS]>,
     pub active_level :LevelParams, 
     pub key_materials :KeyScheme, 
-    pub prp_cipher: CommonCipher    
+    pub prp_cipher: CommonCipher,
+    pub padded :bool,                   // Last block holds padding, removed by decrypt
+    pub max_depth :Option<usize>,       // Number of Shamir levels, None recurses down to parts of a single block
+    pub encode_kernel :EncodeKernel,
+    pub decode_solver :DecodeSolver,
+    out_length :usize
 }
 
 impl <'a> SSCipherCore<'a> {
-    pub fn new(bytes: &[u8],in_length :usize, add_padd :bool, out_bytes :&'a mut Vec<GF128>, targted_threshold:usize,prp_name :CipherName) -> Self {                
+    // Build the structure with fresh random keys and IV (use with_key/with_key_scheme for reproducible results)
+    pub fn new(bytes: &[u8],in_length :usize, add_padd :bool, out_bytes :&'a mut Vec<GF128>, targted_threshold:usize,prp_name :CipherName) -> Result<Self, RsbeError> {                
+        let key1 = GF128::random();
+        let key2 = GF128::random();                                                         
+        Self::with_key(bytes, in_length, add_padd, out_bytes, targted_threshold, prp_name, &[key1,key2], &GF128::random())
+    }
+
+    // Build the structure from a caller supplied key (one or two GF128 elements) and IV, no randomness is drawn
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_key(bytes: &[u8],in_length :usize, add_padd :bool, out_bytes :&'a mut Vec<GF128>, targted_threshold:usize,
+                    prp_name :CipherName, key :&[GF128], iv :&GF128) -> Result<Self, RsbeError> {
+        if key.is_empty() {return Err(RsbeError::InvalidKey)}
+        let key2 = if key.len() == 1 {key[0]} else {key[1]};
+        let prp_cipher = CommonCipher::newcipher(&prp_name, &[key[0].to_u128(),key2.to_u128()]);  
+        let key_scheme = generate_key_scheme(key, iv, targted_threshold,&prp_cipher)?;  
+        Self::with_key_scheme(bytes, in_length, add_padd, out_bytes, targted_threshold, prp_cipher, key_scheme)
+    }
+
+    // Build the structure from an already generated key scheme and the PRP it was generated with
+    pub fn with_key_scheme(bytes: &[u8],in_length :usize, add_padd :bool, out_bytes :&'a mut Vec<GF128>, targted_threshold:usize,
+                           prp_cipher :CommonCipher, key_scheme :KeyScheme) -> Result<Self, RsbeError> {
+        if targted_threshold < MIN_THRESHOLD {return Err(RsbeError::ThresholdTooSmall)}
+        if key_scheme.beta_vector.true_size != 2 * targted_threshold - 1 {return Err(RsbeError::InvalidKey)}
         let length = if in_length==0 {bytes.len()} else {in_length};
+        if length > bytes.len() {return Err(RsbeError::InvalidLength)}
+        if !add_padd && !length.is_multiple_of(16) {return Err(RsbeError::InvalidLength)}     // No partial block without padding
         let blocks_count = (length / 16) + if add_padd {1} else {0};
+        if blocks_count == 0 {return Err(RsbeError::InvalidLength)}
+        fit_thresholds(blocks_count, targted_threshold)?;
         out_bytes.reserve(blocks_count);
         unsafe {       ptr::copy_nonoverlapping(
                                 bytes.as_ptr(),
@@ -119,192 +531,565 @@ impl <'a> SSCipherCore<'a> {
                                 }
                         out_bytes[blocks_count-1] = last_block;
                     }
-        let opt_params = fit_thresholds(blocks_count, targted_threshold);  // Get optimal thresholding parametres 
-        let active_level = LevelParams{    start: 0, end: blocks_count-1,           // Get initial decomposition level parameters
-                                                        blocks_count, threshold: opt_params.0, 
-                                                        max_part_size :opt_params.1,
-                                                        last_part_size : blocks_count % opt_params.1};
-        let mut tmp_vector =SmallVec::<[__m128i; MAX_VECTOR_ELEMENTS]>::new();
-        tmp_vector.resize(MAX_VECTOR_ELEMENTS, GF128::from(0).0);     
-        let key1 = GF128::random();
-        let key2 = GF128::random();                                                         
-        let prp_cipher = CommonCipher::newcipher(&prp_name, &[key1.to_u128(),key2.to_u128()]);  
-        let random_key_scheme = generate_key_scheme(&[key1,key2], &GF128::random(), targted_threshold,&prp_cipher);  
-        SSCipherCore {  targted_threshold ,internal: out_bytes ,decryption_parts_stack :Vec::<LevelParams>::new(), 
-                        active_level, tmp_vector ,key_materials :random_key_scheme, prp_cipher }
+        let mut core = Self::in_place_with_key_scheme(out_bytes, targted_threshold, prp_cipher, key_scheme)?;
+        core.padded = add_padd;
+        Ok(core)
+    }
+
+    // Work directly on caller owned blocks : the data is neither copied nor reallocated, no padding is added and the
+    // ciphertext has exactly the size of the plaintext
+    pub fn in_place(blocks :&'a mut [GF128], targted_threshold :usize, prp_name :CipherName, key :&[GF128], iv :&GF128) -> Result<Self, RsbeError> {
+        if key.is_empty() {return Err(RsbeError::InvalidKey)}
+        let key2 = if key.len() == 1 {key[0]} else {key[1]};
+        let prp_cipher = CommonCipher::newcipher(&prp_name, &[key[0].to_u128(),key2.to_u128()]);  
+        let key_scheme = generate_key_scheme(key, iv, targted_threshold,&prp_cipher)?;  
+        Self::in_place_with_key_scheme(blocks, targted_threshold, prp_cipher, key_scheme)
+    }
+
+    // Same as in_place over a byte buffer, which must be 16-byte aligned (Misaligned otherwise). A partial final block
+    // is rejected with InvalidLength instead of being dropped, arbitrary lengths go through the padded constructors
+    pub fn in_place_bytes(bytes :&'a mut [u8], targted_threshold :usize, prp_name :CipherName, key :&[GF128], iv :&GF128) -> Result<Self, RsbeError> {
+        Self::in_place(aligned_blocks(bytes)?, targted_threshold, prp_name, key, iv)
+    }
+
+    // Same as in_place_bytes from an already generated key scheme and the PRP it was generated with
+    pub fn in_place_bytes_with_key_scheme(bytes :&'a mut [u8], targted_threshold :usize, prp_cipher :CommonCipher, 
+                                          key_scheme :KeyScheme) -> Result<Self, RsbeError> {
+        Self::in_place_with_key_scheme(aligned_blocks(bytes)?, targted_threshold, prp_cipher, key_scheme)
+    }
+
+    pub fn in_place_with_key_scheme(blocks :&'a mut [GF128], targted_threshold :usize, prp_cipher :CommonCipher, 
+                                    key_scheme :KeyScheme) -> Result<Self, RsbeError> {
+        if targted_threshold < MIN_THRESHOLD {return Err(RsbeError::ThresholdTooSmall)}
+        if key_scheme.beta_vector.true_size != 2 * targted_threshold - 1 {return Err(RsbeError::InvalidKey)}
+        let blocks_count = blocks.len();
+        let active_level = LevelParams::top(blocks_count, targted_threshold)?;     // Get initial decomposition level parameters
+        let mut tmp_vector =SmallVec::<[GF128; MAX_VECTOR_ELEMENTS]>::new();
+        tmp_vector.resize((2 * targted_threshold - 1).max(MAX_VECTOR_ELEMENTS), GF128::from(0));     // Heap backed above the inline capacity
+        let solver_scratch = tmp_vector.clone();
+        Ok(SSCipherCore {  targted_threshold ,internal: blocks ,decryption_parts_stack :SmallVec::new(), 
+                        active_level, tmp_vector, solver_scratch, key_materials :key_scheme, prp_cipher,
+                        padded :false, max_depth :None, encode_kernel :EncodeKernel::default(), decode_solver :DecodeSolver::default(), 
+                        out_length :blocks_count * 16 })
     }
     
+    // Bytes of the data, without the padding once decrypted
     pub fn get_bytes_out(&self) -> &[u8] {
         unsafe {    std::slice::from_raw_parts(
                     self.internal.as_ptr() as *const u8,
-                    self.internal.len() * 16
+                    self.out_length
                     )
                 }
     }
 
-    pub fn get_block(&self, index: usize) -> GF128 {
-        if index < self.internal.len() { self.internal[index]}            
-        else {panic!("Index outside the size of data.")}
+    pub fn get_block(&self, index: usize) -> Result<GF128, RsbeError> {
+        if index < self.internal.len() { Ok(self.internal[index])}            
+        else {Err(RsbeError::IndexOutOfRange)}
     }
 
-    pub fn set_block(&mut self, index: usize, value :&GF128) {
-        if index < self.internal.len() {self.internal[index] = *value} 
-        else {panic!("Index outside the size of data.")}
+    pub fn set_block(&mut self, index: usize, value :&GF128) -> Result<(), RsbeError> {
+        if index < self.internal.len() {self.internal[index] = *value; Ok(())} 
+        else {Err(RsbeError::IndexOutOfRange)}
     }
 
     // Define iterator on the structure blocks
     pub fn blocks(&self) -> impl Iterator<Item = GF128 > {        
-        (0..self.active_level.blocks_count).filter_map(move |i| Some(self.get_block(i)))
+        (0..self.active_level.blocks_count).filter_map(move |i| self.get_block(i).ok())
     }
 
-    pub fn set_key_scheme(&mut self,key :&[GF128],iv :&GF128){
+    pub fn set_key_scheme(&mut self,key :&[GF128],iv :&GF128) -> Result<(), RsbeError>{
         
-        self.key_materials = generate_key_scheme(key, iv, self.targted_threshold, &self.prp_cipher);
+        let mask_schedule = self.key_materials.mask_schedule;
+        self.key_materials = generate_key_scheme(key, iv, self.targted_threshold, &self.prp_cipher)?;
+        self.key_materials.mask_schedule = mask_schedule;   // The schedule is a parameter of the core, not of the key
+        Ok(())
     } 
+    // Cap the number of Shamir levels (1 to MAX_DEPTH), all the blocks of the final level are then encrypted with the
+    // PRP. Must match between encryption and decryption, it is recorded by the container header
+    pub fn set_max_depth(&mut self, max_depth :Option<usize>) -> Result<(), RsbeError>{
+        if max_depth.is_some_and(|depth| depth == 0 || depth > MAX_DEPTH) {return Err(RsbeError::InvalidDepth)}
+        self.max_depth = max_depth;
+        Ok(())
+    }
+
+    // Levels and cost of encrypting the data with the current parameters, kernel and mask schedule (see cipher::plan)
+    pub fn level_plan(&self) -> Result<LevelPlan, RsbeError>{
+        level_plan_with(self.internal.len(), self.targted_threshold, self.max_depth, self.encode_kernel, self.key_materials.mask_schedule)
+    }
+
+    // Must match between encryption and decryption, it is recorded by the container and stream headers
+    pub fn set_mask_schedule(&mut self, mask_schedule :MaskSchedule){
+        self.key_materials.mask_schedule = mask_schedule;
+    }
+    // Does not change the ciphertext, only how the levels are encoded
+    pub fn set_encode_kernel(&mut self, encode_kernel :EncodeKernel){
+        self.encode_kernel = encode_kernel;
+    }
+    // Does not change the plaintext, only how the levels are decoded
+    pub fn set_decode_solver(&mut self, decode_solver :DecodeSolver){
+        self.decode_solver = decode_solver;
+    }
+
+    // Pre-scaled matrix of the active level when the interleaved kernel applies to it (level checked by planned_levels)
+    fn scaled_matrix(&self) -> Option<ScaledMatrix> {
+        let key_materials = &self.key_materials;
+        if self.encode_kernel != EncodeKernel::Interleaved || key_materials.mask_schedule != MaskSchedule::Generator {return None}
+        Some(ScaledMatrix::new(&key_materials.vender_matrix, &key_materials.beta_vector, self.active_level.threshold))
+    }
+
+    fn encode_level(&mut self) {
+        let level = self.active_level;
+        let step = MaskStep::encoding(self.key_materials.mask_schedule);
+        let mut alpha = self.key_materials.alpha;
+        let mut beta_vector = self.key_materials.beta_vector.clone();
+        for i in 0..level.max_part_size {
+                unsafe { encode_vector_raw(self.internal.as_mut_ptr(), &level, &self.key_materials.vender_matrix, 
+                                           i, &alpha, &beta_vector, &mut self.tmp_vector) }
+                step.next(&mut alpha, &mut beta_vector, level.threshold);
+            }
+    }
+
+    fn encode_level_interleaved(&mut self, scaled :&ScaledMatrix) {
+        let level = self.active_level;
+        let step = MaskStep::encoding(self.key_materials.mask_schedule);
+        let mut tmp_vector = SmallVec::<[[GF128; INTERLEAVED_VECTORS]; MAX_VECTOR_ELEMENTS]>::from_elem([GF128::from(0); INTERLEAVED_VECTORS], level.threshold);
+        unsafe { encode_range_raw(self.internal.as_mut_ptr(), &level, scaled, 0, level.max_part_size, &self.key_materials.alpha, &step, &mut tmp_vector) }
+    }
+
     pub fn vectors_count(&self)-> usize{
         self.active_level.max_part_size
     }
 
+    // Every block, mask and scratch element the kernels reach for the vector `index` of the active level must exist :
+    // the blocks index + j * max_part_size lie in the level, which lies in the data
+    fn check_vector(&self, index :usize, masks :&GF128Vector) -> Result<(), RsbeError> {
+        let level = &self.active_level;
+        if index >= level.max_part_size || level.blocks_count > self.internal.len() {return Err(RsbeError::IndexOutOfRange)}
+        if level.threshold > self.tmp_vector.len() || level.threshold > masks.elements.len() {return Err(RsbeError::InconsistentDimensions)}
+        Ok(())
+    }
+
+    // Encoded levels from the active one down and the final level, each one checked against the data, the key scheme
+    // and the scratch vectors so that the kernels cannot fail once the first block is written
+    fn planned_levels(&self) -> Result<(SmallVec<[LevelParams; MAX_INLINE_LEVELS]>, LevelParams), RsbeError> {
+        let key_materials = &self.key_materials;
+        let mut levels = SmallVec::new();
+        let mut level = self.active_level;
+        while level.is_encoded(self.max_depth) {
+            if level.blocks_count > self.internal.len() {return Err(RsbeError::IndexOutOfRange)}
+            if [self.tmp_vector.len(), self.solver_scratch.len(), key_materials.vender_matrix.data.len(), key_materials.beta_vector.elements.len(),
+                key_materials.inv_beta_vector.elements.len()].iter().any(|&size| level.threshold > size) {return Err(RsbeError::InconsistentDimensions)}
+            levels.push(level);
+            level.go_down(self.targted_threshold)?;
+        }
+        if level.terminal_blocks() > self.internal.len() {return Err(RsbeError::IndexOutOfRange)}
+        Ok((levels, level))
+    }
+
     #[inline(always)]    
-    pub fn encode_vector(&mut self, index: usize, alpha :&GF128, beta_vector :&GF128Vector) {
-        let threshold = if index < self.active_level.last_part_size {self.active_level.threshold} 
-                               else {self.active_level.threshold - 1};
-        let alpha_val = *alpha;                               
-        let internal_slice = &mut self.internal;
-        let matrix_data = &self.key_materials.vender_matrix.data;
-        let alpha_m128i = alpha_val.0;
-        let tmp_values = &mut self.tmp_vector;    
-        let bv = &beta_vector.elements;
-        unsafe {    let mut j = 0;
-                    for i in 0..threshold {  tmp_values[i] = ((internal_slice[j + index] + alpha_val).multiply(&bv[i])).0;
-                                                    j += self.active_level.max_part_size;
-                                                 }
-                    for i in 0..threshold {
-                        let mut acc_lo = _mm_setzero_si128();
-                        let mut acc_hi = _mm_setzero_si128();                    
-                        let matrix_row = &matrix_data[i];                    
-                        for mj in 0..threshold {     let matrix_elem = matrix_row[mj].0;
-                                                            let internal_elem = tmp_values[mj];                        
-                                                            let h0 = _mm_clmulepi64_si128(matrix_elem, internal_elem, 0x00);
-                                                            let h1 = _mm_clmulepi64_si128(matrix_elem, internal_elem, 0x01);
-                                                            let h2 = _mm_clmulepi64_si128(matrix_elem, internal_elem, 0x10);
-                                                            let h3 = _mm_clmulepi64_si128(matrix_elem, internal_elem, 0x11);                       
-                                                            let h1h2 = _mm_xor_si128(h1, h2);
-                                                            let prod_lo = _mm_xor_si128(h0, _mm_slli_si128(h1h2, 8));
-                                                            let prod_hi = _mm_xor_si128(h3, _mm_srli_si128(h1h2, 8));                        
-                                                            acc_lo = _mm_xor_si128(acc_lo, prod_lo);
-                                                            acc_hi = _mm_xor_si128(acc_hi, prod_hi);
-                                                        }                    
-                        let poly = _mm_set_epi64x(0, 0x87);
-                        let t0 = _mm_clmulepi64_si128(acc_hi, poly, 0x00);
-                        let t1 = _mm_clmulepi64_si128(acc_hi, poly, 0x01);
-                        let v0 = _mm_xor_si128(acc_lo, t0);
-                        let v1 = _mm_xor_si128(v0, _mm_slli_si128(t1, 8));
-                        let t2 = _mm_srli_si128(t1, 8);
-                        let t3 = _mm_clmulepi64_si128(t2, poly, 0x00);
-                        let result = GF128(_mm_xor_si128(v1, t3));
-                        internal_slice[i * self.active_level.max_part_size + index] = GF128(_mm_xor_si128(result.0, alpha_m128i));
-                    }
+    pub fn encode_vector(&mut self, index: usize, alpha :&GF128, beta_vector :&GF128Vector) -> Result<(), RsbeError> {
+        self.check_vector(index, beta_vector)?;
+        if self.active_level.threshold > self.key_materials.vender_matrix.data.len() {return Err(RsbeError::InconsistentDimensions)}
+        unsafe { encode_vector_raw(self.internal.as_mut_ptr(), &self.active_level, &self.key_materials.vender_matrix, 
+                                   index, alpha, beta_vector, &mut self.tmp_vector) }
+        Ok(())
+    }
+
+    // Make the inverse Vandermonde matrices of the given sizes available (done by decrypt before decoding)
+    pub fn prepare_inverses(&mut self, sizes :&[usize]) -> Result<(), RsbeError> {
+        let key_materials = &mut self.key_materials;
+        key_materials.dec_inverses.require(&key_materials.vender_matrix, sizes)
+    }
+
+    // Make the structured solvers of the given sizes available (done by decrypt for the levels that use them)
+    pub fn prepare_solvers(&mut self, sizes :&[usize]) -> Result<(), RsbeError> {
+        self.key_materials.require_solvers(sizes)
+    }
+
+    // Inverses or solvers (following decode_solver) of the sizes of the levels, computed at most once per key scheme
+    fn prepare_levels(&mut self, levels :&[LevelParams]) -> Result<(), RsbeError> {
+        let mut inverse_sizes :SmallVec<[usize; 2 * MAX_INLINE_LEVELS]> = SmallVec::new();
+        let mut solver_sizes :SmallVec<[usize; 2 * MAX_INLINE_LEVELS]> = SmallVec::new();
+        for level in levels {
+            let sizes = if self.decode_solver.is_structured(level) {&mut solver_sizes} else {&mut inverse_sizes};
+            sizes.extend([level.threshold, level.threshold - 1]);
         }
+        self.prepare_inverses(&inverse_sizes)?;
+        self.prepare_solvers(&solver_sizes)
     }
 
-#[inline(always)]    
-pub fn decode_vector(&mut self, index: usize, alpha :&GF128, inv_beta_vector :&GF128Vector) {
-        let threshold = if index < self.active_level.last_part_size {self.active_level.threshold} 
-                               else {self.active_level.threshold - 1};   
-        let matrix = if index < self.active_level.last_part_size {&self.key_materials.principal_dec_matrice} else {&self.key_materials.secondary_dec_matrice};    
-        let alpha_val = *alpha;
-        let internal_slice = &mut self.internal;
-        let matrix_data = &matrix.data;
-        let alpha_m128i = alpha_val.0;
-        let tmp_values = &mut self.tmp_vector;    
-        unsafe {
-            let mut j = 0;
-            for i in 0..threshold {  tmp_values[i] = (internal_slice[j + index] + alpha_val).0;
-                                            j += self.active_level.max_part_size;
-                                        }        
-            for i in 0..threshold {
-                let mut acc_lo = _mm_setzero_si128();
-                let mut acc_hi = _mm_setzero_si128();            
-                let matrix_row = &matrix_data[i];            
-                for mj in 0..threshold {     let matrix_elem = matrix_row[mj].0;
-                                                    let internal_elem = tmp_values[mj];                
-                                                    let h0 = _mm_clmulepi64_si128(matrix_elem, internal_elem, 0x00);
-                                                    let h1 = _mm_clmulepi64_si128(matrix_elem, internal_elem, 0x01);
-                                                    let h2 = _mm_clmulepi64_si128(matrix_elem, internal_elem, 0x10);
-                                                    let h3 = _mm_clmulepi64_si128(matrix_elem, internal_elem, 0x11);                
-                                                    let h1h2 = _mm_xor_si128(h1, h2);
-                                                    let prod_lo = _mm_xor_si128(h0, _mm_slli_si128(h1h2, 8));
-                                                    let prod_hi = _mm_xor_si128(h3, _mm_srli_si128(h1h2, 8));                
-                                                    acc_lo = _mm_xor_si128(acc_lo, prod_lo);
-                                                    acc_hi = _mm_xor_si128(acc_hi, prod_hi);
-                                                }            
-                let poly = _mm_set_epi64x(0, 0x87);
-                let t0 = _mm_clmulepi64_si128(acc_hi, poly, 0x00);
-                let t1 = _mm_clmulepi64_si128(acc_hi, poly, 0x01);
-                let v0 = _mm_xor_si128(acc_lo, t0);
-                let v1 = _mm_xor_si128(v0, _mm_slli_si128(t1, 8));
-                let t2 = _mm_srli_si128(t1, 8);
-                let t3 = _mm_clmulepi64_si128(t2, poly, 0x00);
-                let result = GF128(_mm_xor_si128(v1, t3)).multiply(&inv_beta_vector.elements[i]);            
-                internal_slice[i * self.active_level.max_part_size + index] = GF128(_mm_xor_si128(result.0, alpha_m128i));
+    #[inline(always)]    
+    pub fn decode_vector(&mut self, index: usize, alpha :&GF128, inv_beta_vector :&GF128Vector) -> Result<(), RsbeError> {
+        self.check_vector(index, inv_beta_vector)?;
+        let level = self.active_level;
+        // Prepared once per decryption by decrypt, see prepare_levels
+        match self.key_materials.level_decoder(&level, self.decode_solver.is_structured(&level))? {
+            LevelDecoder::Solvers(full, reduced) => {
+                if level.threshold > self.solver_scratch.len() {return Err(RsbeError::InconsistentDimensions)}
+                unsafe { decode_vector_solved_raw(self.internal.as_mut_ptr(), &level, (full, reduced), index, alpha, inv_beta_vector, 
+                                                  &mut self.tmp_vector, &mut self.solver_scratch) }
+            }
+            LevelDecoder::Inverses(full, reduced) => {
+                unsafe { decode_vector_raw(self.internal.as_mut_ptr(), &level, (full, reduced), 
+                                           index, alpha, inv_beta_vector, &mut self.tmp_vector) }
+            }
+        }
+        Ok(())
+    }
+
+    // Encode all the vectors of the active level, split in chunks over the pool threads. Each chunk starts from the
+    // masks of its first vector, so the result is identical to the sequential loop
+    fn encode_level_parallel(&mut self, pool :&ThreadPool, scaled :Option<&ScaledMatrix>) {
+        let level = self.active_level;
+        let vectors_count = level.max_part_size;
+        let blocks = SharedBlocks(self.internal.as_mut_ptr());
+        let key_materials = &self.key_materials;
+        let step = MaskStep::encoding(key_materials.mask_schedule);
+        let chunk_size = vectors_count.div_ceil(pool.current_num_threads() * 4).max(MIN_PARALLEL_CHUNK);
+        pool.install(|| (0..vectors_count.div_ceil(chunk_size)).into_par_iter().for_each(|chunk| {
+                let start = chunk * chunk_size;
+                if let Some(scaled) = scaled {
+                    let mut tmp_vector = SmallVec::<[[GF128; INTERLEAVED_VECTORS]; MAX_VECTOR_ELEMENTS]>::from_elem([GF128::from(0); INTERLEAVED_VECTORS], level.threshold);
+                    let end = (start + chunk_size).min(vectors_count);
+                    unsafe { encode_range_raw(blocks.get(), &level, scaled, start, end, &key_materials.alpha, &step, &mut tmp_vector) }
+                    return;
+                }
+                let (mut alpha, mut beta_vector) = step.at(&key_materials.alpha, &key_materials.beta_vector, level.threshold, start);
+                let mut tmp_vector = SmallVec::<[GF128; MAX_VECTOR_ELEMENTS]>::from_elem(GF128::from(0), level.threshold.max(MAX_VECTOR_ELEMENTS));
+                for i in start..(start + chunk_size).min(vectors_count) {
+                        unsafe { encode_vector_raw(blocks.get(), &level, &key_materials.vender_matrix, i, &alpha, &beta_vector, &mut tmp_vector) }
+                        step.next(&mut alpha, &mut beta_vector, level.threshold);
+                    }
+            }));
+    }
+
+    fn decode_level(&mut self) -> Result<(), RsbeError> {
+        let level = self.active_level;
+        let key_materials = &self.key_materials;
+        let step = MaskStep::decoding(key_materials.mask_schedule);
+        let decoder = key_materials.level_decoder(&level, self.decode_solver.is_structured(&level))?;
+        let mut alpha = key_materials.alpha;
+        let mut inv_beta_vector = key_materials.inv_beta_vector.clone();
+        for i in 0..level.max_part_size {
+                match decoder {
+                    LevelDecoder::Solvers(full, reduced) => unsafe { decode_vector_solved_raw(self.internal.as_mut_ptr(), &level, (full, reduced), i, &alpha, 
+                                                                     &inv_beta_vector, &mut self.tmp_vector, &mut self.solver_scratch) },
+                    LevelDecoder::Inverses(full, reduced) => unsafe { decode_vector_raw(self.internal.as_mut_ptr(), &level, (full, reduced), i, &alpha, 
+                                                                      &inv_beta_vector, &mut self.tmp_vector) },
+                }
+                step.next(&mut alpha, &mut inv_beta_vector, level.threshold);
             }
+        Ok(())
+    }
+
+    fn decode_level_parallel(&mut self, pool :&ThreadPool) -> Result<(), RsbeError> {
+        let level = self.active_level;
+        let vectors_count = level.max_part_size;
+        let blocks = SharedBlocks(self.internal.as_mut_ptr());
+        let key_materials = &self.key_materials;
+        let step = MaskStep::decoding(key_materials.mask_schedule);
+        let decoder = key_materials.level_decoder(&level, self.decode_solver.is_structured(&level))?;
+        let structured = matches!(decoder, LevelDecoder::Solvers(..));
+        let chunk_size = vectors_count.div_ceil(pool.current_num_threads() * 4).max(MIN_PARALLEL_CHUNK);
+        pool.install(|| (0..vectors_count.div_ceil(chunk_size)).into_par_iter().for_each(|chunk| {
+                let start = chunk * chunk_size;
+                let (mut alpha, mut inv_beta_vector) = step.at(&key_materials.alpha, &key_materials.inv_beta_vector, level.threshold, start);
+                let mut tmp_vector = SmallVec::<[GF128; MAX_VECTOR_ELEMENTS]>::from_elem(GF128::from(0), level.threshold.max(MAX_VECTOR_ELEMENTS));
+                let mut scratch = if structured {tmp_vector.clone()} else {SmallVec::new()};
+                for i in start..(start + chunk_size).min(vectors_count) {
+                        match decoder {
+                            LevelDecoder::Solvers(full, reduced) => unsafe { decode_vector_solved_raw(blocks.get(), &level, (full, reduced), i, &alpha, &inv_beta_vector, &mut tmp_vector, &mut scratch) },
+                            LevelDecoder::Inverses(full, reduced) => unsafe { decode_vector_raw(blocks.get(), &level, (full, reduced), i, &alpha, &inv_beta_vector, &mut tmp_vector) },
+                        }
+                        step.next(&mut alpha, &mut inv_beta_vector, level.threshold);
+                    }
+            }));
+        Ok(())
+    }
+
+    // PRP encryption of the first `count` blocks chained from the IV (CBC), which for a single block is the usual
+    // terminal step
+    fn prp_chain_encrypt(&mut self, count :usize) {
+        let mut chain = self.key_materials.iv;
+        for block in self.internal[..count].iter_mut() {
+            *block = block.addto(&chain).prp_encrypt(&self.prp_cipher);
+            chain = *block;
         }
     }
 
-    pub fn encrypt(&mut self){        
+    fn prp_chain_decrypt(&mut self, count :usize) {
+        let mut chain = self.key_materials.iv;
+        for block in self.internal[..count].iter_mut() {
+            let encrypted = *block;
+            *block = encrypted.prp_decrypt(&self.prp_cipher).addto(&chain);
+            chain = encrypted;
+        }
+    }
+
+    pub fn encrypt(&mut self) -> Result<(), RsbeError>{        
+        self.encrypt_on(None)
+    }
+
+    // Same as encrypt, the vectors of each level being encoded in parallel on the given pool (byte-identical output)
+    pub fn par_encrypt(&mut self, pool :&ThreadPool) -> Result<(), RsbeError>{        
+        self.encrypt_on(Some(pool))
+    }
+
+    fn encrypt_on(&mut self, pool :Option<&ThreadPool>) -> Result<(), RsbeError>{        
         let save_part = self.active_level;
-        while self.active_level.max_part_size > 1
-                {  
-                    let mut alpha = self.key_materials.alpha; 
-                    let vectors_count = self.active_level.max_part_size;
-                    let mut beta_vector = self.key_materials.beta_vector.clone();
-                    for i in  0..vectors_count{
-                                self.encode_vector(i, &alpha, &beta_vector);                                                                
-                                alpha = alpha.multiply(&alpha);
-                                for k in 0..self.active_level.threshold
-                                                { beta_vector.elements[k] = beta_vector.elements[k].multiply(&beta_vector.elements[k])}
-                                }                
-                    self.active_level.go_down(self.targted_threshold);
+        let result = self.encrypt_levels(pool);
+        self.active_level = save_part;
+        result
+    }
+
+    // Nothing fails once the levels are planned, an error leaves the data untouched
+    fn encrypt_levels(&mut self, pool :Option<&ThreadPool>) -> Result<(), RsbeError>{        
+        let (levels, final_level) = self.planned_levels()?;
+        for level in levels {
+                    self.active_level = level;
+                    let scaled = self.scaled_matrix();
+                    if let Some(pool) = pool { self.encode_level_parallel(pool, scaled.as_ref()); }
+                    else if let Some(scaled) = &scaled { self.encode_level_interleaved(scaled); }
+                    else { self.encode_level(); }
                 }
-            let last_level_block = self.get_block(0);
-            let encrypted_last_block = last_level_block.addto(&self.key_materials.iv).prp_encrypt (&self.prp_cipher);
-            self.set_block(0, &encrypted_last_block);
-            self.active_level = save_part;
+            self.prp_chain_encrypt(final_level.terminal_blocks());
+            self.out_length = self.internal.len() * 16;
+            Ok(())
     }
 
     
-    pub fn decrypt(&mut self){
-        fn get_levels(this : &mut SSCipherCore){
-                if this.active_level.max_part_size > 1 {
-                    this.decryption_parts_stack.push(this.active_level);
-                    this.active_level.go_down(this.targted_threshold);
-                    get_levels(this); 
-                    }
-            }        
-        get_levels(self);
-        let first_level_block = self.get_block(0);
-        let decrypted_first_block = first_level_block.prp_decrypt(&self.prp_cipher).addto(&self.key_materials.iv);
-        self.set_block(0, &decrypted_first_block); 
-        while ! self.decryption_parts_stack.is_empty(){                
-                self.active_level = self.decryption_parts_stack.pop().unwrap();
-                let vectors_count = self.active_level.max_part_size;
-                let mut alpha = self.key_materials.alpha.clone();
-                let mut inv_beta_vector = self.key_materials.inv_beta_vector.clone();
-                (self.key_materials.principal_dec_matrice,self.key_materials.secondary_dec_matrice) = 
-                        self.key_materials.vender_matrix.invert_vandermonde_both(self.active_level.threshold);
-                for i in 0..vectors_count 
-                            {   self.decode_vector(i, &alpha, &inv_beta_vector);
-                                alpha = alpha.multiply(&alpha);
-                                for k in 0..self.active_level.threshold
-                                                { inv_beta_vector.elements[k] = inv_beta_vector.elements[k].multiply(&inv_beta_vector.elements[k])}
-                            }
+    pub fn decrypt(&mut self) -> Result<(), RsbeError>{
+        self.decrypt_on(None)
+    }
+
+    // Same as decrypt, the vectors of each level being decoded in parallel on the given pool
+    pub fn par_decrypt(&mut self, pool :&ThreadPool) -> Result<(), RsbeError>{
+        self.decrypt_on(Some(pool))
+    }
+
+    fn decrypt_on(&mut self, pool :Option<&ThreadPool>) -> Result<(), RsbeError>{
+        let save_part = self.active_level;
+        let result = self.decrypt_levels(pool);
+        self.active_level = save_part;
+        self.decryption_parts_stack.clear();
+        result
+    }
+
+    // The levels are planned and their inverses or solvers prepared before the first block is written
+    fn decrypt_levels(&mut self, pool :Option<&ThreadPool>) -> Result<(), RsbeError>{
+        let (levels, final_level) = self.planned_levels()?;
+        self.prepare_levels(&levels)?;
+        self.decryption_parts_stack.extend_from_slice(&levels);
+        self.prp_chain_decrypt(final_level.terminal_blocks());
+        while let Some(level) = self.decryption_parts_stack.pop() {
+                self.active_level = level;
+                if let Some(pool) = pool { self.decode_level_parallel(pool)?; }
+                else { self.decode_level()?; }
             }
-            // // Remove padding
-            // if let Some(&pad_len) = self.internal.last() {   interface.out_bytes
-            //                                                                 .resize(interface.out_bytes.len()
-            //                                                                 .saturating_sub(pad_len as usize), 0);
-            //                                                             }     
+        let mut out_length = self.internal.len() * 16;
+        // Remove padding
+        if self.padded {    let last_block = self.internal.last().ok_or(RsbeError::InvalidLength)?;
+                            out_length -= padding_size(&last_block.to_u128().to_le_bytes())?;
+                        }
+        self.out_length = out_length;
+        Ok(())
     }    
 }
 
+
+
+// Blocks of a 16-byte aligned byte buffer whose length is a multiple of 16
+fn aligned_blocks(bytes :&mut [u8]) -> Result<&mut [GF128], RsbeError> {
+    if !bytes.len().is_multiple_of(16) {return Err(RsbeError::InvalidLength)}
+    // GF128 is a plain 16 bytes vector, any bit pattern is valid
+    let (prefix, blocks, suffix) = unsafe { bytes.align_to_mut::<GF128>() };
+    if !prefix.is_empty() {return Err(RsbeError::Misaligned)}
+    if !suffix.is_empty() {return Err(RsbeError::InvalidLength)}
+    Ok(blocks)
+}
+
+//  Owned high-level interface over SSCipherCore: holds the key and the PRP choice, draws a fresh IV for
+//  each message and returns a self-describing container (see cipher::container), so callers only deal with byte slices
+pub struct Rsbe {
+    key : Vec<GF128>,
+    prp_name :CipherName,
+    targted_threshold :usize,
+    key_id :Option<Vec<u8>>,
+    mask_schedule :MaskSchedule,
+    max_depth :Option<usize>,
+    pool :Option<ThreadPool>,
+    // Key scheme of the last message with the PRP it was generated for, and the inverses or solvers prepared by its
+    // decryption. The nodes and masks derive from the IV : it is reused by the messages sharing that IV and threshold,
+    // e.g. decrypting what was just encrypted or opening the same container again
+    key_scheme :Mutex<Option<(CipherName, KeyScheme)>>
+}
+
+impl Drop for Rsbe {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl fmt::Debug for Rsbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rsbe").field("prp_name", &self.prp_name).field("targted_threshold", &self.targted_threshold)
+                              .field("key_id", &self.key_id).field("mask_schedule", &self.mask_schedule)
+                              .field("max_depth", &self.max_depth).finish_non_exhaustive()
+    }
+}
+
+impl Rsbe {
+    pub fn new(key :&[GF128], prp_name :CipherName, targted_threshold :usize) -> Result<Self, RsbeError> {
+        if key.is_empty() {return Err(RsbeError::InvalidKey)}
+        if targted_threshold < MIN_THRESHOLD {return Err(RsbeError::ThresholdTooSmall)}
+        if targted_threshold > MAX_THRESHOLD {return Err(RsbeError::ThresholdTooLarge)}
+        Ok(Rsbe { key :key.to_vec(), prp_name, targted_threshold, key_id :None, mask_schedule :MaskSchedule::Generator, max_depth :None, pool :None,
+                  key_scheme :Mutex::new(None) })
+    }
+
+    // Key derived from a passphrase with kdf::passphrase_kdf, the PRP is the one of the KDF parameters
+    pub fn from_passphrase(passphrase :&[u8], salt :&[u8], params :&PassphraseParams, targted_threshold :usize) -> Result<Self, RsbeError> {
+        let key = Zeroizing::new(passphrase_kdf(passphrase, salt, params, 2)?);
+        Self::new(&key, params.prp_name, targted_threshold)
+    }
+
+    // Identifier recorded in the containers produced by this instance, and checked when decrypting : containers with
+    // another key id or none are rejected
+    pub fn set_key_id(&mut self, key_id :&[u8]) -> Result<(), RsbeError> {
+        if key_id.is_empty() || key_id.len() > 255 {return Err(RsbeError::InvalidKey)}
+        self.key_id = Some(key_id.to_vec());
+        Ok(())
+    }
+
+    // Mask schedule of the containers produced by this instance, decryption follows the one of each container
+    pub fn set_mask_schedule(&mut self, mask_schedule :MaskSchedule) {
+        self.mask_schedule = mask_schedule;
+    }
+
+    // Recursion depth of the containers produced by this instance (see SSCipherCore::set_max_depth), decryption
+    // follows the one of each container
+    pub fn set_max_depth(&mut self, max_depth :Option<usize>) -> Result<(), RsbeError> {
+        if max_depth.is_some_and(|depth| depth == 0 || depth > MAX_DEPTH) {return Err(RsbeError::InvalidDepth)}
+        self.max_depth = max_depth;
+        Ok(())
+    }
+
+    // Number of threads used to process the vectors of each level, 0 lets rayon choose, 1 restores the sequential path
+    pub fn set_threads(&mut self, threads :usize) -> Result<(), RsbeError> {
+        if threads == 1 {self.pool = None; return Ok(())}
+        let pool = ThreadPoolBuilder::new().num_threads(threads).build().map_err(|e| RsbeError::ThreadPoolError(e.to_string()))?;
+        self.pool = Some(pool);
+        Ok(())
+    }
+
+    // PRP of the key, and the key scheme of the message kept from the previous one when it has the same PRP, IV and
+    // threshold, a new one otherwise
+    fn key_scheme(&self, prp_name :CipherName, iv :&GF128, targted_threshold :usize) -> Result<(CommonCipher, KeyScheme), RsbeError> {
+        let key2 = if self.key.len() == 1 {self.key[0]} else {self.key[1]};
+        let prp_cipher = CommonCipher::newcipher(&prp_name, &[self.key[0].to_u128(), key2.to_u128()]);
+        let kept = self.key_scheme.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        match kept {
+            Some((name, key_scheme)) if name == prp_name && key_scheme.iv == *iv 
+                                        && key_scheme.beta_vector.true_size == 2 * targted_threshold - 1 => Ok((prp_cipher, key_scheme)),
+            _ => {  let key_scheme = generate_key_scheme(&self.key, iv, targted_threshold, &prp_cipher)?;
+                    Ok((prp_cipher, key_scheme))
+                }
+        }
+    }
+
+    fn keep_key_scheme(&self, prp_name :CipherName, key_scheme :KeyScheme) {
+        *self.key_scheme.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some((prp_name, key_scheme));
+    }
+
+    // Encrypt an arbitrary length message, output layout is : RsbeHeader || padded ciphertext blocks
+    pub fn encrypt(&self, plaintext :&[u8]) -> Result<Vec<u8>, RsbeError> {
+        self.seal(plaintext, None)
+    }
+
+    // Authenticated encryption, output layout is : RsbeHeader || padded ciphertext blocks || tag. The tag covers the
+    // header, the associated data (not included in the output) and the ciphertext
+    pub fn encrypt_aead(&self, plaintext :&[u8], associated_data :&[u8]) -> Result<Vec<u8>, RsbeError> {
+        self.seal(plaintext, Some(associated_data))
+    }
+
+    fn seal(&self, plaintext :&[u8], associated_data :Option<&[u8]>) -> Result<Vec<u8>, RsbeError> {
+        let iv = GF128::random();
+        let mut blocks = Vec::<GF128>::new();
+        let (prp_cipher, key_scheme) = self.key_scheme(self.prp_name, &iv, self.targted_threshold)?;
+        let mut core = SSCipherCore::with_key_scheme(plaintext, plaintext.len(), true, &mut blocks, self.targted_threshold, prp_cipher, key_scheme)?;
+        core.set_mask_schedule(self.mask_schedule);
+        core.set_max_depth(self.max_depth)?;
+        match &self.pool { Some(pool) => core.par_encrypt(pool)?, None => core.encrypt()? }
+        let mut header = RsbeHeader::new(self.prp_name, self.targted_threshold, iv, plaintext.len() as u64, self.key_id.clone());
+        header.authenticated = associated_data.is_some();
+        header.mask_schedule = self.mask_schedule;
+        header.max_depth = self.max_depth;
+        let mut result = Vec::with_capacity(header.size() + core.internal.len() * 16 + header.tag_size());
+        header.write_to(&mut result)?;
+        result.extend_from_slice(core.get_bytes_out());
+        if let Some(associated_data) = associated_data {
+            let tag = compute_tag(&core.prp_cipher, self.prp_name, &iv, &[&result[..header.size()], associated_data], core.get_bytes_out())?;
+            result.extend_from_slice(&tag);
+        }
+        self.keep_key_scheme(self.prp_name, core.key_materials);
+        Ok(result)
+    }
+
+    // Decrypt a container, the PRP and threshold are taken from its header and not from this instance. An
+    // authenticated container is verified with empty associated data
+    pub fn decrypt(&self, ciphertext :&[u8]) -> Result<Vec<u8>, RsbeError> {
+        self.open(ciphertext, None)
+    }
+
+    // Authenticated decryption, no plaintext is released unless the tag verifies
+    pub fn decrypt_aead(&self, ciphertext :&[u8], associated_data :&[u8]) -> Result<Vec<u8>, RsbeError> {
+        self.open(ciphertext, Some(associated_data))
+    }
+
+    fn open(&self, ciphertext :&[u8], associated_data :Option<&[u8]>) -> Result<Vec<u8>, RsbeError> {
+        let mut blocks = Vec::<GF128>::new();
+        let (header, body) = container_body(ciphertext)?;
+        // An instance with a key id only opens containers carrying the same one
+        if let Some(expected) = &self.key_id && header.key_id.as_ref() != Some(expected) {return Err(RsbeError::KeyIdMismatch)}
+        let (prp_cipher, key_scheme) = self.key_scheme(header.prp_name, &header.iv, header.targted_threshold)?;
+        let mut core = SSCipherCore::with_key_scheme(body, body.len(), false, &mut blocks, header.targted_threshold, prp_cipher, key_scheme)?;
+        header.configure(&mut core)?;
+        if associated_data.is_some() && !header.authenticated {return Err(RsbeError::AuthenticationFailed)}
+        if header.authenticated {
+            let body = &ciphertext[header.size()..];
+            let (blocks_bytes, tag) = body.split_at(header.ciphertext_size()?);
+            let expected = compute_tag(&core.prp_cipher, header.prp_name, &header.iv, &[&ciphertext[..header.size()], associated_data.unwrap_or(&[])],
+                                       blocks_bytes)?;
+            if !verify_tag(&expected, tag) {return Err(RsbeError::AuthenticationFailed)}
+        }
+        match &self.pool { Some(pool) => core.par_decrypt(pool)?, None => core.decrypt()? }
+        let plaintext = core.get_bytes_out();
+        if plaintext.len() as u64 != header.plaintext_length {return Err(RsbeError::InvalidLength)}
+        let plaintext = plaintext.to_vec();
+        self.keep_key_scheme(header.prp_name, core.key_materials);
+        Ok(plaintext)
+    }
+
+    // Encrypt a 16-byte aligned buffer in place (length multiple of 16, no padding, no header), returns the fresh IV
+    // that must be kept along with the buffer to decrypt it
+    pub fn encrypt_in_place(&self, buffer :&mut [u8]) -> Result<GF128, RsbeError> {
+        let iv = GF128::random();
+        let (prp_cipher, key_scheme) = self.key_scheme(self.prp_name, &iv, self.targted_threshold)?;
+        let mut core = SSCipherCore::in_place_bytes_with_key_scheme(buffer, self.targted_threshold, prp_cipher, key_scheme)?;
+        core.set_mask_schedule(self.mask_schedule);
+        core.set_max_depth(self.max_depth)?;
+        match &self.pool { Some(pool) => core.par_encrypt(pool)?, None => core.encrypt()? }
+        self.keep_key_scheme(self.prp_name, core.key_materials);
+        Ok(iv)
+    }
+
+    pub fn decrypt_in_place(&self, buffer :&mut [u8], iv :&GF128) -> Result<(), RsbeError> {
+        let (prp_cipher, key_scheme) = self.key_scheme(self.prp_name, iv, self.targted_threshold)?;
+        let mut core = SSCipherCore::in_place_bytes_with_key_scheme(buffer, self.targted_threshold, prp_cipher, key_scheme)?;
+        core.set_mask_schedule(self.mask_schedule);
+        core.set_max_depth(self.max_depth)?;
+        match &self.pool { Some(pool) => core.par_decrypt(pool)?, None => core.decrypt()? }
+        self.keep_key_scheme(self.prp_name, core.key_materials);
+        Ok(())
+    }
+}
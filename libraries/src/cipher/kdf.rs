@@ -0,0 +1,165 @@
+use zeroize::{Zeroize, Zeroizing};
+use crate::{common_ciphers::{CipherName, CommonCipher}, error::RsbeError, GF128};
+
+// Key derivation built only on the block ciphers of common_ciphers, no external crypto dependency.
+//
+//  - cmac / kdf_counter / derive_keys : CMAC (SP 800-38B) as PRF of the SP 800-108 counter mode KDF, to derive
+//    per-object keys from a master secret with a label and a context
+//  - passphrase_kdf : salted, iterated and memory-hard passphrase KDF following the Balloon hashing construction,
+//    for interactive use. Its output is a key as expected by generate_key_scheme / SSCipherCore::with_key / Rsbe::new
+//
+// Blocks are read as big-endian u128, the output keys are GF128 elements built from such blocks.
+
+// Doubling in GF(2^128) used by the CMAC subkeys (x^128 = x^7 + x^2 + x + 1)
+#[inline(always)]
+fn dbl(x: u128) -> u128 {
+    (x << 1) ^ if x >> 127 == 1 {0x87} else {0}
+}
+
+// Incremental CMAC over a 128-bit block cipher
+struct Cmac<'a> {
+    prp: &'a CommonCipher,
+    state: u128,
+    pending: [u8; 16],
+    pending_size: usize,
+}
+
+impl<'a> Cmac<'a> {
+    fn new(prp: &'a CommonCipher) -> Self {
+        Cmac { prp, state: 0, pending: [0; 16], pending_size: 0 }
+    }
+
+    // The last (possibly full) block is always kept pending, it gets a subkey in finalize
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.pending_size == 16 {
+                self.state = self.prp.encrypt_block(self.state ^ u128::from_be_bytes(self.pending));
+                self.pending_size = 0;
+            }
+            let take = (16 - self.pending_size).min(data.len());
+            self.pending[self.pending_size..self.pending_size + take].copy_from_slice(&data[..take]);
+            self.pending_size += take;
+            data = &data[take..];
+        }
+    }
+
+    fn finalize(mut self) -> u128 {
+        let k1 = dbl(self.prp.encrypt_block(0));
+        let last = if self.pending_size == 16 {u128::from_be_bytes(self.pending) ^ k1}
+                   else {   self.pending[self.pending_size] = 0x80;
+                            self.pending[self.pending_size + 1..].fill(0);
+                            u128::from_be_bytes(self.pending) ^ dbl(k1)
+                        };
+        let tag = self.prp.encrypt_block(self.state ^ last);
+        self.state.zeroize();
+        self.pending.zeroize();
+        tag
+    }
+}
+
+// CMAC of the concatenation of the given parts
+pub fn cmac(prp: &CommonCipher, parts: &[&[u8]]) -> u128 {
+    let mut mac = Cmac::new(prp);
+    for part in parts {mac.update(part)}
+    mac.finalize()
+}
+
+// SP 800-108 KDF in counter mode with CMAC as PRF : K(i) = CMAC(i || label || 0x00 || context || L), i and L
+// (output length in bits) being 32-bit big-endian integers. Fills `out` entirely
+pub fn kdf_counter(prp: &CommonCipher, label: &[u8], context: &[u8], out: &mut [u8]) -> Result<(), RsbeError> {
+    let length_bits = u32::try_from(out.len() * 8).map_err(|_| RsbeError::InvalidKdfParameters)?;
+    for (i, chunk) in out.chunks_mut(16).enumerate() {
+        let counter = (i as u32 + 1).to_be_bytes();
+        let block = cmac(prp, &[&counter, label, &[0], context, &length_bits.to_be_bytes()]).to_be_bytes();
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+    Ok(())
+}
+
+// Derive `keys_count` GF128 keys from a master key (one or two elements, used as the key of the PRP)
+pub fn derive_keys(master_key: &[GF128], prp_name: CipherName, label: &[u8], context: &[u8], keys_count: usize) -> Result<Vec<GF128>, RsbeError> {
+    if master_key.is_empty() {return Err(RsbeError::InvalidKey)}
+    if keys_count == 0 {return Err(RsbeError::InvalidKdfParameters)}
+    let key2 = if master_key.len() == 1 {master_key[0]} else {master_key[1]};
+    let prp = CommonCipher::newcipher(&prp_name, &[master_key[0].to_u128(), key2.to_u128()]);
+    let mut bytes = Zeroizing::new(vec![0u8; keys_count * 16]);
+    kdf_counter(&prp, label, context, &mut bytes)?;
+    Ok(bytes.chunks(16).map(|b| GF128::from(u128::from_be_bytes(b.try_into().unwrap()))).collect())
+}
+
+// Cost parameters of passphrase_kdf : memory_blocks * 16 bytes are kept in memory and each one is rewritten
+// `iterations` times, mixing with DELTA pseudo-randomly chosen blocks at each rewrite
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassphraseParams {
+    pub memory_blocks: usize,
+    pub iterations: usize,
+    pub prp_name: CipherName,
+}
+
+impl Default for PassphraseParams {
+    // 512 KiB, 2 passes over AES-256 : about one second with the table based AES
+    fn default() -> Self {
+        PassphraseParams { memory_blocks: 1 << 15, iterations: 2, prp_name: CipherName::AES256 }
+    }
+}
+
+const DELTA: usize = 3;
+pub const MIN_SALT_SIZE: usize = 16;
+
+// Davies-Meyer compression with the block cipher keyed by the message blocks, used to turn the passphrase and the
+// salt into cipher keys before the memory-hard part
+fn dm_hash(prp_name: CipherName, iv: u128, parts: &[&[u8]]) -> u128 {
+    let mut data = Zeroizing::new(Vec::new());
+    for part in parts {data.extend_from_slice(&(part.len() as u64).to_be_bytes()); data.extend_from_slice(part);}
+    data.push(0x80);
+    let padded_size = data.len().div_ceil(16) * 16;
+    data.resize(padded_size, 0);
+    let mut h = iv;
+    for block in data.chunks(16) {
+        let m = u128::from_be_bytes(block.try_into().unwrap());
+        h ^= CommonCipher::newcipher(&prp_name, &[m, m]).encrypt_block(h);
+    }
+    h
+}
+
+// Passphrase KDF for interactive use (Balloon hashing over CMAC). The buffer is filled and mixed with a CMAC keyed
+// from the passphrase, the indices of the mixed blocks depend only on the salt (data-independent memory accesses).
+// Returns `keys_count` GF128 keys, 2 for a full key of the 256-bit PRPs
+pub fn passphrase_kdf(passphrase: &[u8], salt: &[u8], params: &PassphraseParams, keys_count: usize) -> Result<Vec<GF128>, RsbeError> {
+    let s = params.memory_blocks;
+    if s < 2 || params.iterations == 0 || keys_count == 0 {return Err(RsbeError::InvalidKdfParameters)}
+    if salt.len() < MIN_SALT_SIZE {return Err(RsbeError::InvalidKdfParameters)}
+    let name = params.prp_name;
+    let mut key = [dm_hash(name, 1, &[salt, passphrase]), dm_hash(name, 2, &[salt, passphrase])];
+    let prp = CommonCipher::newcipher(&name, &key);
+    let index_prp = CommonCipher::newcipher(&name, &[dm_hash(name, 3, &[salt]), dm_hash(name, 4, &[salt])]);
+    let mut buffer = Zeroizing::new(vec![0u128; s]);
+    let mut counter = 0u64;
+    let mut hash = |parts: &[&[u8]]| {  counter += 1;
+                                        let mut mac = Cmac::new(&prp);
+                                        mac.update(&counter.to_be_bytes());
+                                        for part in parts {mac.update(part)}
+                                        mac.finalize()
+                                    };
+    // Expand
+    buffer[0] = hash(&[salt]);
+    for m in 1..s {buffer[m] = hash(&[&buffer[m - 1].to_be_bytes()])}
+    // Mix
+    for t in 0..params.iterations {
+        for m in 0..s {
+            let previous = buffer[(m + s - 1) % s];
+            buffer[m] = hash(&[&previous.to_be_bytes(), &buffer[m].to_be_bytes()]);
+            for i in 0..DELTA {
+                let index = cmac(&index_prp, &[&(t as u64).to_be_bytes(), &(m as u64).to_be_bytes(), &(i as u64).to_be_bytes()]);
+                let other = buffer[(index % s as u128) as usize];
+                buffer[m] = hash(&[&buffer[m].to_be_bytes(), &other.to_be_bytes()]);
+            }
+        }
+    }
+    // Extract
+    let output_prp = CommonCipher::newcipher(&name, &[buffer[s - 1], key[0] ^ key[1]]);
+    key.zeroize();
+    let mut bytes = Zeroizing::new(vec![0u8; keys_count * 16]);
+    kdf_counter(&output_prp, b"RSBE passphrase", salt, &mut bytes)?;
+    Ok(bytes.chunks(16).map(|b| GF128::from(u128::from_be_bytes(b.try_into().unwrap()))).collect())
+}
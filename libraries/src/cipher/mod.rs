@@ -1,3 +1,9 @@
 pub mod core;
 pub mod cbc_core;
-pub mod ctr_core;
\ No newline at end of file
+pub mod ctr_core;
+pub mod padding;
+pub mod container;
+pub mod aead;
+pub mod kdf;
+pub mod stream;
+pub mod plan;
\ No newline at end of file
@@ -0,0 +1,20 @@
+use crate::error::RsbeError;
+
+// Returns 0xFF when a < b and 0x00 otherwise, without branching on the values
+#[inline(always)]
+fn ct_lt(a: u8, b: u8) -> u8 {
+    ((a as u16).wrapping_sub(b as u16) >> 8) as u8
+}
+
+// Check the PKCS#7-style padding of the last plaintext block and return the padding size.
+// Every byte of the block is inspected whatever the padding value, so the time taken does not
+// depend on where (or whether) the padding is malformed
+pub fn padding_size(last_block: &[u8; 16]) -> Result<usize, RsbeError> {
+    let pad = last_block[15];
+    let mut bad = !ct_lt(0, pad) | !ct_lt(pad, 17);
+    for i in 0..16 {
+        let in_pad = ct_lt(i as u8, pad);
+        bad |= in_pad & (last_block[15 - i] ^ pad);
+    }
+    if bad == 0 { Ok(pad as usize) } else { Err(RsbeError::InvalidPadding) }
+}
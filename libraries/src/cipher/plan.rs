@@ -0,0 +1,79 @@
+use std::fmt;
+use crate::{cipher::core::{EncodeKernel, LevelParams, MaskSchedule, MIN_THRESHOLD}, error::RsbeError};
+
+// Dry run of the recursive decomposition : how a message of a given number of blocks is split into levels by
+// fit_thresholds / LevelParams::go_down, and what encrypting (or decrypting) it costs, without touching any data.
+//
+// In a level, the vectors of index < last_part_size hold `threshold` blocks and are decoded with the inverse of the
+// full Vandermonde prefix (principal), the others hold `threshold - 1` blocks and use the smaller prefix (secondary).
+// The GF multiplications counted are those of the encryption with the kernel that applies to the level :
+//  - Single (and any kernel under the Frobenius schedule) : k*k + k for a vector of k blocks (matrix product and beta
+//    masks), moving the masks to the next vector costs 1 + threshold more
+//  - Interleaved : k*k + k as well (matrix product, then one scaling per output), moving the masks costs 2, and
+//    building the scaled matrix threshold*threshold per level
+// Decrypting with the inverses (DecodeSolver::Inverses) costs the Single figure, plus the inverses built once per key
+// scheme.
+
+#[derive(Clone, Copy, Debug)]
+pub struct LevelSummary {
+    pub params: LevelParams,
+    pub principal_vectors: usize,
+    pub secondary_vectors: usize,
+    pub gf_multiplications: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct LevelPlan {
+    pub blocks_count: usize,
+    pub targted_threshold: usize,
+    pub max_depth: Option<usize>,
+    pub kernel: EncodeKernel,           // Kernel the costs are counted for, Single under the Frobenius schedule
+    pub levels: Vec<LevelSummary>,      // Shamir encoded levels, from the top one
+    pub terminal_blocks: usize,         // Blocks of the final level encrypted with the PRP
+    pub gf_multiplications: usize,
+    pub prp_calls: usize,               // PRP calls on the data, the key scheme generation is not counted
+}
+
+// Level plan of `blocks_count` blocks (length / 16 + 1 for a padded message of `length` bytes), with the same rules
+// as SSCipherCore::encrypt and decrypt, and the costs of the default kernel under the Generator schedule of Rsbe
+pub fn level_plan(blocks_count: usize, targted_threshold: usize, max_depth: Option<usize>) -> Result<LevelPlan, RsbeError> {
+    level_plan_with(blocks_count, targted_threshold, max_depth, EncodeKernel::default(), MaskSchedule::Generator)
+}
+
+// Same as level_plan, with the costs of the given kernel and mask schedule
+pub fn level_plan_with(blocks_count: usize, targted_threshold: usize, max_depth: Option<usize>, kernel: EncodeKernel,
+                       mask_schedule: MaskSchedule) -> Result<LevelPlan, RsbeError> {
+    if targted_threshold < MIN_THRESHOLD {return Err(RsbeError::ThresholdTooSmall)}
+    let kernel = if mask_schedule == MaskSchedule::Generator {kernel} else {EncodeKernel::Single};
+    let mut level = LevelParams::top(blocks_count, targted_threshold)?;
+    let mut levels = Vec::new();
+    while level.is_encoded(max_depth) {
+        let principal_vectors = level.last_part_size;
+        let secondary_vectors = level.max_part_size - level.last_part_size;
+        let (t, vectors) = (level.threshold, level.max_part_size);
+        let vectors_cost = principal_vectors * (t * t + t) + secondary_vectors * ((t - 1) * (t - 1) + t - 1);
+        let gf_multiplications = match kernel {
+            EncodeKernel::Single => vectors_cost + vectors * (1 + t),
+            EncodeKernel::Interleaved => vectors_cost + vectors * 2 + t * t,
+        };
+        levels.push(LevelSummary { params: level, principal_vectors, secondary_vectors, gf_multiplications });
+        level.go_down(targted_threshold)?;
+    }
+    let terminal_blocks = level.terminal_blocks();
+    let gf_multiplications = levels.iter().map(|summary| summary.gf_multiplications).sum();
+    Ok(LevelPlan { blocks_count, targted_threshold, max_depth, kernel, levels, terminal_blocks, gf_multiplications, prp_calls: terminal_blocks })
+}
+
+impl fmt::Display for LevelPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} blocks, targeted threshold {}, max depth {:?}, {:?} kernel", self.blocks_count, self.targted_threshold, self.max_depth, self.kernel)?;
+        writeln!(f, "{:>6} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>14}", "level", "blocks", "threshold", "part size",
+                 "last part", "principal", "secondary", "GF mults")?;
+        for summary in &self.levels {
+            let level = &summary.params;
+            writeln!(f, "{:>6} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>14}", level.depth, level.blocks_count, level.threshold,
+                     level.max_part_size, level.last_part_size, summary.principal_vectors, summary.secondary_vectors, summary.gf_multiplications)?;
+        }
+        write!(f, "Total : {} GF multiplications, {} PRP calls ({} terminal blocks)", self.gf_multiplications, self.prp_calls, self.terminal_blocks)
+    }
+}
@@ -0,0 +1,236 @@
+use std::io::{self, Read, Write};
+use zeroize::Zeroizing;
+use crate::{cipher::core::{MaskSchedule, SSCipherCore, MAX_DEPTH, MAX_THRESHOLD, MIN_THRESHOLD}, common_ciphers::{CipherName, CommonCipher}, error::RsbeError, GF128};
+
+// Segmented streaming mode for inputs that do not fit in memory. The plaintext is cut into segments of
+// `segment_size` bytes, each one is encrypted as an independent RSBE message under its own IV derived from
+// the master IV, so the stream can be produced and decrypted one segment at a time.
+//
+// Stream layout (integers are big-endian) :
+//
+//  offset  size  field
+//  0       4     magic "RSBS"
+//  4       1     format version (currently 1)
+//  5       1     PRP identifier (CipherName::id)
+//  6       1     mask schedule (MaskSchedule::id)
+//  7       1     maximal recursion depth (1..=255), 0 when it is not capped
+//  8       2     targeted threshold
+//  10      4     segment size in plaintext bytes (non zero multiple of 16, at most MAX_SEGMENT_SIZE)
+//  14      16    master IV
+//  30      ...   frames
+//
+// Each frame is : flag (1 byte, 0 = more frames follow, 1 = final frame) || ciphertext length (4 bytes) || ciphertext.
+// Non final frames hold exactly `segment size` plaintext bytes without padding. The final frame holds the
+// remaining 0..=segment size bytes, padded, so its ciphertext length is a multiple of 16 up to segment size + 16.
+// The IV of segment i is PRP(master IV ^ (i << 1 | final)), a stream that ends without a final frame is rejected.
+
+pub const STREAM_MAGIC: [u8; 4] = *b"RSBS";
+pub const STREAM_FORMAT_VERSION: u8 = 1;
+pub const DEFAULT_SEGMENT_SIZE: usize = 1 << 20;
+// The reader allocates a segment from the untrusted header, larger segments are rejected
+pub const MAX_SEGMENT_SIZE: usize = 1 << 26;
+const STREAM_HEADER_SIZE: usize = 30;
+
+fn invalid_data(e: RsbeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn segment_iv(master_iv: &GF128, index: u64, last: bool, prp: &CommonCipher) -> GF128 {
+    GF128::from(master_iv.to_u128() ^ ((index as u128) << 1 | last as u128)).prp_encrypt(prp)
+}
+
+fn key_prp(prp_name: CipherName, key: &[GF128]) -> CommonCipher {
+    let key2 = if key.len() == 1 {key[0]} else {key[1]};
+    CommonCipher::newcipher(&prp_name, &[key[0].to_u128(), key2.to_u128()])
+}
+
+// Encrypting writer : plaintext written to it is emitted as framed segments into `inner`.
+// `finish` must be called to emit the final frame, otherwise the stream is incomplete.
+pub struct StreamEncryptor<W: Write> {
+    inner: W,
+    key: Zeroizing<Vec<GF128>>,
+    prp_name: CipherName,
+    prp: CommonCipher,
+    targted_threshold: usize,
+    mask_schedule: MaskSchedule,
+    max_depth: Option<usize>,
+    master_iv: GF128,
+    segment_size: usize,
+    segment_index: u64,
+    buffer: Vec<u8>,
+    blocks: Vec<GF128>,
+}
+
+impl<W: Write> StreamEncryptor<W> {
+    // The mask schedule and the recursion depth (see SSCipherCore::set_max_depth) are recorded in the header
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(mut inner: W, key: &[GF128], prp_name: CipherName, targted_threshold: usize, segment_size: usize, master_iv: &GF128,
+               mask_schedule: MaskSchedule, max_depth: Option<usize>) -> io::Result<Self> {
+        if key.is_empty() {return Err(invalid_data(RsbeError::InvalidKey))}
+        if targted_threshold < MIN_THRESHOLD {return Err(invalid_data(RsbeError::ThresholdTooSmall))}
+        if targted_threshold > MAX_THRESHOLD {return Err(invalid_data(RsbeError::ThresholdTooLarge))}
+        if max_depth.is_some_and(|depth| depth == 0 || depth > MAX_DEPTH) {return Err(invalid_data(RsbeError::InvalidDepth))}
+        let threshold = u16::try_from(targted_threshold).map_err(|_| invalid_data(RsbeError::InvalidHeader))?;
+        let size = u32::try_from(segment_size).map_err(|_| invalid_data(RsbeError::InvalidLength))?;
+        if segment_size == 0 || !segment_size.is_multiple_of(16) || segment_size > MAX_SEGMENT_SIZE {return Err(invalid_data(RsbeError::InvalidLength))}
+        let mut header = Vec::with_capacity(STREAM_HEADER_SIZE);
+        header.extend_from_slice(&STREAM_MAGIC);
+        header.push(STREAM_FORMAT_VERSION);
+        header.push(prp_name.id());
+        header.extend_from_slice(&[mask_schedule.id(), max_depth.unwrap_or(0) as u8]);
+        header.extend_from_slice(&threshold.to_be_bytes());
+        header.extend_from_slice(&size.to_be_bytes());
+        header.extend_from_slice(&master_iv.to_bytes());
+        inner.write_all(&header)?;
+        Ok(StreamEncryptor { inner, key: Zeroizing::new(key.to_vec()), prp_name, prp: key_prp(prp_name, key), targted_threshold,
+                             mask_schedule, max_depth, master_iv: *master_iv, segment_size, segment_index: 0,
+                             buffer: Vec::with_capacity(segment_size), blocks: Vec::new() })
+    }
+
+    fn emit_segment(&mut self, plaintext_len: usize, last: bool) -> io::Result<()> {
+        let iv = segment_iv(&self.master_iv, self.segment_index, last, &self.prp);
+        self.blocks.clear();
+        let mut core = SSCipherCore::with_key(&self.buffer[..plaintext_len], plaintext_len, last, &mut self.blocks,
+                                              self.targted_threshold, self.prp_name, &self.key, &iv).map_err(invalid_data)?;
+        core.set_mask_schedule(self.mask_schedule);
+        core.set_max_depth(self.max_depth).map_err(invalid_data)?;
+        core.encrypt().map_err(invalid_data)?;
+        let ciphertext = core.get_bytes_out();
+        self.inner.write_all(&[last as u8])?;
+        self.inner.write_all(&(ciphertext.len() as u32).to_be_bytes())?;
+        self.inner.write_all(ciphertext)?;
+        self.buffer.drain(..plaintext_len);
+        self.segment_index += 1;
+        Ok(())
+    }
+
+    // Emit the remaining plaintext as the final frame and give back the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let remaining = self.buffer.len();
+        self.emit_segment(remaining, true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for StreamEncryptor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A full segment is only emitted once more data follows it, the last one is left to `finish`
+        let room = self.segment_size + 1 - self.buffer.len();
+        let taken = buf.len().min(room);
+        self.buffer.extend_from_slice(&buf[..taken]);
+        if self.buffer.len() > self.segment_size {self.emit_segment(self.segment_size, false)?}
+        Ok(taken)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Decrypting reader : reads a framed stream from `inner` and yields the plaintext, one segment at a time
+pub struct StreamDecryptor<R: Read> {
+    inner: R,
+    key: Zeroizing<Vec<GF128>>,
+    prp_name: CipherName,
+    prp: CommonCipher,
+    targted_threshold: usize,
+    mask_schedule: MaskSchedule,
+    max_depth: Option<usize>,
+    master_iv: GF128,
+    segment_size: usize,
+    segment_index: u64,
+    plaintext: Vec<u8>,
+    position: usize,
+    finished: bool,
+    ciphertext: Vec<u8>,
+    blocks: Vec<GF128>,
+}
+
+impl<R: Read> StreamDecryptor<R> {
+    // Read and check the stream header, the PRP, threshold, segment size, mask schedule and recursion depth are taken
+    // from it
+    pub fn new(mut inner: R, key: &[GF128]) -> io::Result<Self> {
+        if key.is_empty() {return Err(invalid_data(RsbeError::InvalidKey))}
+        let mut header = [0u8; STREAM_HEADER_SIZE];
+        inner.read_exact(&mut header)?;
+        if header[0..4] != STREAM_MAGIC {return Err(invalid_data(RsbeError::InvalidHeader))}
+        if header[4] != STREAM_FORMAT_VERSION {return Err(invalid_data(RsbeError::UnsupportedVersion(header[4])))}
+        let prp_name = CipherName::from_id(header[5]).ok_or(invalid_data(RsbeError::UnknownCipher(header[5])))?;
+        let mask_schedule = MaskSchedule::from_id(header[6]).ok_or(invalid_data(RsbeError::InvalidHeader))?;
+        let max_depth = if header[7] == 0 {None} else {Some(header[7] as usize)};
+        let targted_threshold = u16::from_be_bytes([header[8], header[9]]) as usize;
+        if targted_threshold < MIN_THRESHOLD {return Err(invalid_data(RsbeError::ThresholdTooSmall))}
+        if targted_threshold > MAX_THRESHOLD {return Err(invalid_data(RsbeError::ThresholdTooLarge))}
+        let segment_size = u32::from_be_bytes(header[10..14].try_into().unwrap()) as usize;
+        if segment_size == 0 || !segment_size.is_multiple_of(16) || segment_size > MAX_SEGMENT_SIZE {return Err(invalid_data(RsbeError::InvalidHeader))}
+        let master_iv = GF128::from(<&[u8; 16]>::try_from(&header[14..30]).unwrap());
+        Ok(StreamDecryptor { inner, key: Zeroizing::new(key.to_vec()), prp_name, prp: key_prp(prp_name, key), targted_threshold,
+                             mask_schedule, max_depth, master_iv, segment_size, segment_index: 0, plaintext: Vec::new(), position: 0,
+                             finished: false, ciphertext: Vec::new(), blocks: Vec::new() })
+    }
+
+    // Read, check and decrypt the next frame, returns false once the final frame has been consumed
+    fn next_segment(&mut self) -> io::Result<bool> {
+        if self.finished {return Ok(false)}
+        let mut frame_header = [0u8; 5];
+        self.inner.read_exact(&mut frame_header)?;
+        let last = match frame_header[0] { 0 => false, 1 => true, _ => return Err(invalid_data(RsbeError::InvalidHeader)) };
+        let length = u32::from_be_bytes(frame_header[1..5].try_into().unwrap()) as usize;
+        let valid_length = if last {length >= 16 && length <= self.segment_size + 16 && length.is_multiple_of(16)}
+                           else {length == self.segment_size};
+        if !valid_length {return Err(invalid_data(RsbeError::InvalidLength))}
+        self.ciphertext.resize(length, 0);
+        self.inner.read_exact(&mut self.ciphertext)?;
+        let iv = segment_iv(&self.master_iv, self.segment_index, last, &self.prp);
+        self.blocks.clear();
+        let mut core = SSCipherCore::with_key(&self.ciphertext, length, false, &mut self.blocks,
+                                              self.targted_threshold, self.prp_name, &self.key, &iv).map_err(invalid_data)?;
+        core.padded = last;
+        core.set_mask_schedule(self.mask_schedule);
+        core.set_max_depth(self.max_depth).map_err(invalid_data)?;
+        core.decrypt().map_err(invalid_data)?;
+        self.plaintext.clear();
+        self.plaintext.extend_from_slice(core.get_bytes_out());
+        self.position = 0;
+        self.segment_index += 1;
+        if last {   // Nothing may follow the final frame
+                    self.finished = true;
+                    if self.inner.read(&mut [0u8; 1])? != 0 {return Err(invalid_data(RsbeError::InvalidLength))}
+                }
+        Ok(true)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for StreamDecryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if !self.next_segment()? {return Ok(0)}
+        }
+        let count = buf.len().min(self.plaintext.len() - self.position);
+        buf[..count].copy_from_slice(&self.plaintext[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+// Encrypt everything from `reader` into `writer` as a framed stream with the Generator mask schedule and no depth
+// cap (see StreamEncryptor::new for the other settings), returns the number of plaintext bytes
+pub fn encrypt_stream<R: Read, W: Write>(reader: &mut R, writer: W, key: &[GF128], prp_name: CipherName,
+                                         targted_threshold: usize, segment_size: usize) -> io::Result<u64> {
+    let mut encryptor = StreamEncryptor::new(writer, key, prp_name, targted_threshold, segment_size, &GF128::random(),
+                                             MaskSchedule::Generator, None)?;
+    let count = io::copy(reader, &mut encryptor)?;
+    encryptor.finish()?;
+    Ok(count)
+}
+
+// Decrypt a framed stream from `reader` into `writer`, returns the number of plaintext bytes
+pub fn decrypt_stream<R: Read, W: Write>(reader: R, writer: &mut W, key: &[GF128]) -> io::Result<u64> {
+    let mut decryptor = StreamDecryptor::new(reader, key)?;
+    io::copy(&mut decryptor, writer)
+}
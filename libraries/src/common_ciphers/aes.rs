@@ -1,4 +1,6 @@
-use super::CipherInterface;
+use std::fmt;
+use zeroize::Zeroize;
+use super::{aes_bitsliced, aes_ni::{AesBackend, AesNiKeys}, CipherInterface};
 
 // AES S-Box lookup table
 const SBOX: [u8; 256] = [
@@ -45,12 +47,40 @@ const RCON: [u8; 11] = [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x
 
 pub struct AES128 {
     round_keys: [[u8; 16]; 11], // 11 round keys (original + 10 rounds)
+    ni: Option<AesNiKeys<11>>,  // Same keys for the AES-NI path, None on the table based one
+}
+
+impl Drop for AES128 {
+    fn drop(&mut self) {
+        self.round_keys.zeroize();
+    }
+}
+
+impl fmt::Debug for AES128 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AES128").finish_non_exhaustive()
+    }
 }
 
 impl AES128 {
        pub const NAME: &'static str = "AES";
+
+    // AES-NI is only used when available, the table based code otherwise
+    pub fn with_backend(key: &[u128], backend: AesBackend) -> Self {
+        let mut aes = AES128 {round_keys: [[0u8; 16]; 11], ni: None};
+        aes.key_expansion(key[0]);
+        if backend == AesBackend::AesNi {aes.ni = AesNiKeys::new(&aes.round_keys)}
+        aes
+    }
+
+    pub fn backend(&self) -> AesBackend {
+        if self.ni.is_some() {AesBackend::AesNi} else {AesBackend::Table}
+    }
+
     fn key_expansion(&mut self, key: u128) {        
         let key_bytes = key.to_be_bytes();        
+        // Hardened builds never index the S-box table with key bytes, the AES-NI round keys included
+        if cfg!(feature = "hardened") {aes_bitsliced::expand_key_bytes(&key_bytes, &mut self.round_keys); return}
         self.round_keys[0].copy_from_slice(&key_bytes);
         for round in 1..11 {
             let prev_key = &self.round_keys[round - 1];
@@ -221,11 +251,10 @@ impl CipherInterface for AES128{
     fn level(&self) -> u16 {128}
 
     fn new(key: &[u128]) -> Self {
-        let mut aes = AES128 {round_keys: [[0u8; 16]; 11]};
-        aes.key_expansion(key[0]);
-        aes
+        AES128::with_backend(key, AesBackend::detect())
     }
     fn encrypt_block(&self, input: u128) -> u128 {
+        if let Some(ni) = &self.ni {return ni.encrypt_block(input)}
         let mut state = u128_to_state(input);
         add_round_key(&mut state, &self.round_keys[0]);
         for round in 1..10 {
@@ -240,6 +269,7 @@ impl CipherInterface for AES128{
         state_to_u128(&state)
     }
     fn decrypt_block(&self, input: u128) -> u128 {
+        if let Some(ni) = &self.ni {return ni.decrypt_block(input)}
         let mut state = u128_to_state(input);
         add_round_key(&mut state, &self.round_keys[10]);
         for round in (1..10).rev() {
@@ -1,4 +1,6 @@
-use super::CipherInterface;
+use std::fmt;
+use zeroize::Zeroize;
+use super::{aes_bitsliced, aes_ni::{AesBackend, AesNiKeys}, CipherInterface};
 
 // AES S-Box lookup table (same as AES-128)
 const SBOX: [u8; 256] = [
@@ -45,16 +47,50 @@ const RCON: [u8; 15] = [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x
 
 pub struct AES256 {
     round_keys: [[u8; 16]; 15], // 15 round keys (original + 14 rounds)
+    ni: Option<AesNiKeys<15>>,  // Same keys for the AES-NI path, None on the table based one
+}
+
+impl Drop for AES256 {
+    fn drop(&mut self) {
+        self.round_keys.zeroize();
+    }
+}
+
+impl fmt::Debug for AES256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AES256").finish_non_exhaustive()
+    }
 }
 
 impl AES256 {
     pub const NAME: &'static str = "AES256";
 
+    // AES-NI is only used when available, the table based code otherwise
+    pub fn with_backend(key: &[u128], backend: AesBackend) -> Self {
+        let mut aes = AES256 {round_keys: [[0u8; 16]; 15], ni: None};
+        aes.key_expansion((key[0], key[1]));
+        if backend == AesBackend::AesNi {aes.ni = AesNiKeys::new(&aes.round_keys)}
+        aes
+    }
+
+    pub fn backend(&self) -> AesBackend {
+        if self.ni.is_some() {AesBackend::AesNi} else {AesBackend::Table}
+    }
+
     // AES-256 key expansion - more complex than AES-128
     fn key_expansion(&mut self, key: (u128, u128)) {
         // Split the 256-bit key into two 128-bit parts
         let key_high = key.0.to_be_bytes();
         let key_low = key.1.to_be_bytes();
+        // Hardened builds never index the S-box table with key bytes, the AES-NI round keys included
+        if cfg!(feature = "hardened") {
+            let mut key_bytes = [0u8; 32];
+            key_bytes[..16].copy_from_slice(&key_high);
+            key_bytes[16..].copy_from_slice(&key_low);
+            aes_bitsliced::expand_key_bytes(&key_bytes, &mut self.round_keys);
+            key_bytes.zeroize();
+            return
+        }
         
         // First two round keys are the original key
         self.round_keys[0].copy_from_slice(&key_high);
@@ -276,15 +312,12 @@ impl CipherInterface for AES256 {
     // You'll need to modify this signature in your trait definition
     // This assumes the trait has been updated to handle 256-bit keys
     fn new(key: &[u128]) -> Self {
-        let mut aes = AES256 {
-            round_keys: [[0u8; 16]; 15]
-        };
-        aes.key_expansion((key[0],key[1]));
-        aes
+        AES256::with_backend(key, AesBackend::detect())
     }
 
 
     fn encrypt_block(&self, input: u128) -> u128 {
+        if let Some(ni) = &self.ni {return ni.encrypt_block(input)}
         let mut state = u128_to_state(input);
         
         // Initial round
@@ -307,6 +340,7 @@ impl CipherInterface for AES256 {
     }
 
     fn decrypt_block(&self, input: u128) -> u128 {
+        if let Some(ni) = &self.ni {return ni.decrypt_block(input)}
         let mut state = u128_to_state(input);
         
         // Initial round (inverse of final encryption round)
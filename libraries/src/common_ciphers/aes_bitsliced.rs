@@ -0,0 +1,486 @@
+use std::fmt;
+use zeroize::Zeroize;
+use super::CipherInterface;
+
+// Bitsliced AES-128 and AES-256 without any table nor secret dependent branch (64-bit planes, 4 blocks per pass, in
+// the way of the BearSSL "ct64" implementation). The state of 4 blocks is held in 8 u64, q[i] gathering the bit i
+// of every byte, the S-box is a boolean circuit (Boyar-Peralta) evaluated on the 8 planes at once. Same results as
+// AES128 / AES256, encrypt_blocks and decrypt_blocks amortize a pass over up to 4 blocks.
+
+pub const BITSLICED_BLOCKS: usize = 4;
+
+// Round keys in bitsliced form, R = number of rounds + 1 (boxed, they would make CommonCipher much larger)
+struct BitslicedKeys<const R: usize>(Box<[[u64; 8]; R]>);
+
+impl<const R: usize> Drop for BitslicedKeys<R> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+// Key expansion of FIPS-197 over little-endian 32-bit words, SubWord going through the bitsliced S-box. Fills all of
+// `words`, 4 per round key
+fn expand_words(key: &[u8], words: &mut [u32]) {
+    const RCON: [u32; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+    let nk = key.len() / 4;
+    for (i, word) in key.chunks_exact(4).enumerate() {words[i] = u32::from_le_bytes(word.try_into().unwrap())}
+    let mut tmp = words[nk - 1];
+    let (mut j, mut k) = (0, 0);
+    for i in nk..words.len() {
+        if j == 0 {
+            tmp = sub_word(tmp.rotate_right(8)) ^ RCON[k];
+        } else if nk > 6 && j == 4 {
+            tmp = sub_word(tmp);
+        }
+        tmp ^= words[i - nk];
+        words[i] = tmp;
+        j += 1;
+        if j == nk {j = 0; k += 1;}
+    }
+    tmp.zeroize();
+}
+
+// Round keys in the byte order of FIPS-197 (that of AES128 / AES256), expanded without any table : the AES-NI round keys
+// of the hardened builds come from here
+pub(crate) fn expand_key_bytes(key: &[u8], round_keys: &mut [[u8; 16]]) {
+    let mut words = [0u32; 60];
+    let words = &mut words[..4 * round_keys.len()];
+    expand_words(key, words);
+    for (round_key, round_words) in round_keys.iter_mut().zip(words.chunks_exact(4)) {
+        for (bytes, word) in round_key.chunks_exact_mut(4).zip(round_words) {bytes.copy_from_slice(&word.to_le_bytes())}
+    }
+    words.zeroize();
+}
+
+impl<const R: usize> BitslicedKeys<R> {
+    fn new(key: &[u8]) -> Self {
+        let mut words = [0u32; 60];
+        expand_words(key, &mut words[..4 * R]);
+        let mut keys = BitslicedKeys(Box::new([[0u64; 8]; R]));
+        for (round, round_key) in keys.0.iter_mut().enumerate() {
+            // The same key in the 4 block slots
+            let (q0, q4) = interleave_in(&words[4 * round..4 * round + 4]);
+            *round_key = [q0, q0, q0, q0, q4, q4, q4, q4];
+            ortho(round_key);
+        }
+        words.zeroize();
+        keys
+    }
+
+    fn encrypt(&self, blocks: &mut [u128]) {
+        for chunk in blocks.chunks_mut(BITSLICED_BLOCKS) {
+            let mut q = load(chunk);
+            add_round_key(&mut q, &self.0[0]);
+            for round_key in &self.0[1..R - 1] {
+                sbox(&mut q);
+                shift_rows(&mut q);
+                mix_columns(&mut q);
+                add_round_key(&mut q, round_key);
+            }
+            sbox(&mut q);
+            shift_rows(&mut q);
+            add_round_key(&mut q, &self.0[R - 1]);
+            store(&mut q, chunk);
+        }
+    }
+
+    fn decrypt(&self, blocks: &mut [u128]) {
+        for chunk in blocks.chunks_mut(BITSLICED_BLOCKS) {
+            let mut q = load(chunk);
+            add_round_key(&mut q, &self.0[R - 1]);
+            for round_key in self.0[1..R - 1].iter().rev() {
+                inv_shift_rows(&mut q);
+                inv_sbox(&mut q);
+                add_round_key(&mut q, round_key);
+                inv_mix_columns(&mut q);
+            }
+            inv_shift_rows(&mut q);
+            inv_sbox(&mut q);
+            add_round_key(&mut q, &self.0[0]);
+            store(&mut q, chunk);
+        }
+    }
+}
+
+// Little-endian 32-bit words of a block, byte 0 being the most significant byte of the u128
+#[inline(always)]
+fn block_words(block: u128) -> [u32; 4] {
+    let bytes = block.to_be_bytes();
+    std::array::from_fn(|i| u32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap()))
+}
+
+#[inline(always)]
+fn words_block(words: &[u32; 4]) -> u128 {
+    let mut bytes = [0u8; 16];
+    for (i, word) in words.iter().enumerate() {bytes[4 * i..4 * i + 4].copy_from_slice(&word.to_le_bytes())}
+    u128::from_be_bytes(bytes)
+}
+
+// Up to 4 blocks into the bitsliced state, block i going to q[i] and q[i + 4] before the orthogonalization
+#[inline(always)]
+fn load(blocks: &[u128]) -> [u64; 8] {
+    let mut q = [0u64; 8];
+    for (i, block) in blocks.iter().enumerate() {(q[i], q[i + 4]) = interleave_in(&block_words(*block))}
+    ortho(&mut q);
+    q
+}
+
+#[inline(always)]
+fn store(q: &mut [u64; 8], blocks: &mut [u128]) {
+    ortho(q);
+    for (i, block) in blocks.iter_mut().enumerate() {*block = words_block(&interleave_out(q[i], q[i + 4]))}
+    q.zeroize();
+}
+
+#[inline(always)]
+fn interleave_in(w: &[u32]) -> (u64, u64) {
+    let mut x = [w[0] as u64, w[1] as u64, w[2] as u64, w[3] as u64];
+    for v in x.iter_mut() {
+        *v |= *v << 16;
+        *v &= 0x0000_FFFF_0000_FFFF;
+        *v |= *v << 8;
+        *v &= 0x00FF_00FF_00FF_00FF;
+    }
+    (x[0] | (x[2] << 8), x[1] | (x[3] << 8))
+}
+
+#[inline(always)]
+fn interleave_out(q0: u64, q1: u64) -> [u32; 4] {
+    let mut x = [q0 & 0x00FF_00FF_00FF_00FF, q1 & 0x00FF_00FF_00FF_00FF, (q0 >> 8) & 0x00FF_00FF_00FF_00FF, (q1 >> 8) & 0x00FF_00FF_00FF_00FF];
+    for v in x.iter_mut() {
+        *v |= *v >> 8;
+        *v &= 0x0000_FFFF_0000_FFFF;
+    }
+    x.map(|v| v as u32 | (v >> 16) as u32)
+}
+
+// Transposition between the interleaved blocks and the bit planes (an involution)
+#[inline(always)]
+fn ortho(q: &mut [u64; 8]) {
+    #[inline(always)]
+    fn swap(q: &mut [u64; 8], i: usize, j: usize, low: u64, shift: u32) {
+        let (a, b) = (q[i], q[j]);
+        let high = !low;
+        q[i] = (a & low) | ((b & low) << shift);
+        q[j] = ((a & high) >> shift) | (b & high);
+    }
+    for (i, j) in [(0, 1), (2, 3), (4, 5), (6, 7)] {swap(q, i, j, 0x5555_5555_5555_5555, 1)}
+    for (i, j) in [(0, 2), (1, 3), (4, 6), (5, 7)] {swap(q, i, j, 0x3333_3333_3333_3333, 2)}
+    for (i, j) in [(0, 4), (1, 5), (2, 6), (3, 7)] {swap(q, i, j, 0x0F0F_0F0F_0F0F_0F0F, 4)}
+}
+
+// S-box on the 32-bit word x (key expansion)
+fn sub_word(x: u32) -> u32 {
+    let mut q = [0u64; 8];
+    q[0] = x as u64;
+    ortho(&mut q);
+    sbox(&mut q);
+    ortho(&mut q);
+    let result = q[0] as u32;
+    q.zeroize();
+    result
+}
+
+#[inline(always)]
+fn add_round_key(q: &mut [u64; 8], round_key: &[u64; 8]) {
+    for i in 0..8 {q[i] ^= round_key[i]}
+}
+
+// AES S-box as the 113 gates circuit of Boyar and Peralta, x0 being the most significant bit
+#[inline(always)]
+fn sbox(q: &mut [u64; 8]) {
+    let (x0, x1, x2, x3, x4, x5, x6, x7) = (q[7], q[6], q[5], q[4], q[3], q[2], q[1], q[0]);
+    // Top linear transformation
+    let y14 = x3 ^ x5;
+    let y13 = x0 ^ x6;
+    let y9 = x0 ^ x3;
+    let y8 = x0 ^ x5;
+    let t0 = x1 ^ x2;
+    let y1 = t0 ^ x7;
+    let y4 = y1 ^ x3;
+    let y12 = y13 ^ y14;
+    let y2 = y1 ^ x0;
+    let y5 = y1 ^ x6;
+    let y3 = y5 ^ y8;
+    let t1 = x4 ^ y12;
+    let y15 = t1 ^ x5;
+    let y20 = t1 ^ x1;
+    let y6 = y15 ^ x7;
+    let y10 = y15 ^ t0;
+    let y11 = y20 ^ y9;
+    let y7 = x7 ^ y11;
+    let y17 = y10 ^ y11;
+    let y19 = y10 ^ y8;
+    let y16 = t0 ^ y11;
+    let y21 = y13 ^ y16;
+    let y18 = x0 ^ y16;
+    // Non-linear section
+    let t2 = y12 & y15;
+    let t3 = y3 & y6;
+    let t4 = t3 ^ t2;
+    let t5 = y4 & x7;
+    let t6 = t5 ^ t2;
+    let t7 = y13 & y16;
+    let t8 = y5 & y1;
+    let t9 = t8 ^ t7;
+    let t10 = y2 & y7;
+    let t11 = t10 ^ t7;
+    let t12 = y9 & y11;
+    let t13 = y14 & y17;
+    let t14 = t13 ^ t12;
+    let t15 = y8 & y10;
+    let t16 = t15 ^ t12;
+    let t17 = t4 ^ t14;
+    let t18 = t6 ^ t16;
+    let t19 = t9 ^ t14;
+    let t20 = t11 ^ t16;
+    let t21 = t17 ^ y20;
+    let t22 = t18 ^ y19;
+    let t23 = t19 ^ y21;
+    let t24 = t20 ^ y18;
+    let t25 = t21 ^ t22;
+    let t26 = t21 & t23;
+    let t27 = t24 ^ t26;
+    let t28 = t25 & t27;
+    let t29 = t28 ^ t22;
+    let t30 = t23 ^ t24;
+    let t31 = t22 ^ t26;
+    let t32 = t31 & t30;
+    let t33 = t32 ^ t24;
+    let t34 = t23 ^ t33;
+    let t35 = t27 ^ t33;
+    let t36 = t24 & t35;
+    let t37 = t36 ^ t34;
+    let t38 = t27 ^ t36;
+    let t39 = t29 & t38;
+    let t40 = t25 ^ t39;
+    let t41 = t40 ^ t37;
+    let t42 = t29 ^ t33;
+    let t43 = t29 ^ t40;
+    let t44 = t33 ^ t37;
+    let t45 = t42 ^ t41;
+    let z0 = t44 & y15;
+    let z1 = t37 & y6;
+    let z2 = t33 & x7;
+    let z3 = t43 & y16;
+    let z4 = t40 & y1;
+    let z5 = t29 & y7;
+    let z6 = t42 & y11;
+    let z7 = t45 & y17;
+    let z8 = t41 & y10;
+    let z9 = t44 & y12;
+    let z10 = t37 & y3;
+    let z11 = t33 & y4;
+    let z12 = t43 & y13;
+    let z13 = t40 & y5;
+    let z14 = t29 & y2;
+    let z15 = t42 & y9;
+    let z16 = t45 & y14;
+    let z17 = t41 & y8;
+    // Bottom linear transformation
+    let t46 = z15 ^ z16;
+    let t47 = z10 ^ z11;
+    let t48 = z5 ^ z13;
+    let t49 = z9 ^ z10;
+    let t50 = z2 ^ z12;
+    let t51 = z2 ^ z5;
+    let t52 = z7 ^ z8;
+    let t53 = z0 ^ z3;
+    let t54 = z6 ^ z7;
+    let t55 = z16 ^ z17;
+    let t56 = z12 ^ t48;
+    let t57 = t50 ^ t53;
+    let t58 = z4 ^ t46;
+    let t59 = z3 ^ t54;
+    let t60 = t46 ^ t57;
+    let t61 = z14 ^ t57;
+    let t62 = t52 ^ t58;
+    let t63 = t49 ^ t58;
+    let t64 = z4 ^ t59;
+    let t65 = t61 ^ t62;
+    let t66 = z1 ^ t63;
+    let s0 = t59 ^ t63;
+    let s6 = t56 ^ !t62;
+    let s7 = t48 ^ !t60;
+    let t67 = t64 ^ t65;
+    let s3 = t53 ^ t66;
+    let s4 = t51 ^ t66;
+    let s5 = t47 ^ t65;
+    let s1 = t64 ^ !s3;
+    let s2 = t55 ^ !t67;
+    *q = [s7, s6, s5, s4, s3, s2, s1, s0];
+}
+
+// Inverse S-box : the inverse affine map, the forward S-box, and the inverse affine map again (the forward affine map
+// then cancels out, leaving the field inversion)
+#[inline(always)]
+fn inv_sbox(q: &mut [u64; 8]) {
+    #[inline(always)]
+    fn inv_affine(q: &mut [u64; 8]) {
+        let (q0, q1, q2, q3, q4, q5, q6, q7) = (!q[0], !q[1], q[2], q[3], q[4], !q[5], !q[6], q[7]);
+        *q = [q2 ^ q5 ^ q7, q3 ^ q6 ^ q0, q4 ^ q7 ^ q1, q5 ^ q0 ^ q2, q6 ^ q1 ^ q3, q7 ^ q2 ^ q4, q0 ^ q3 ^ q5, q1 ^ q4 ^ q6];
+    }
+    inv_affine(q);
+    sbox(q);
+    inv_affine(q);
+}
+
+#[inline(always)]
+fn shift_rows(q: &mut [u64; 8]) {
+    for x in q.iter_mut() {
+        *x = (*x & 0x0000_0000_0000_FFFF)
+            | ((*x & 0x0000_0000_FFF0_0000) >> 4)
+            | ((*x & 0x0000_0000_000F_0000) << 12)
+            | ((*x & 0x0000_FF00_0000_0000) >> 8)
+            | ((*x & 0x0000_00FF_0000_0000) << 8)
+            | ((*x & 0xF000_0000_0000_0000) >> 12)
+            | ((*x & 0x0FFF_0000_0000_0000) << 4);
+    }
+}
+
+#[inline(always)]
+fn inv_shift_rows(q: &mut [u64; 8]) {
+    for x in q.iter_mut() {
+        *x = (*x & 0x0000_0000_0000_FFFF)
+            | ((*x & 0x0000_0000_0FFF_0000) << 4)
+            | ((*x & 0x0000_0000_F000_0000) >> 12)
+            | ((*x & 0x0000_00FF_0000_0000) << 8)
+            | ((*x & 0x0000_FF00_0000_0000) >> 8)
+            | ((*x & 0x000F_0000_0000_0000) << 12)
+            | ((*x & 0xFFF0_0000_0000_0000) >> 4);
+    }
+}
+
+// Rotation of the columns by two rows, rotate_right(16) being the rotation by one row
+#[inline(always)]
+fn rotr32(x: u64) -> u64 {
+    x.rotate_right(32)
+}
+
+#[inline(always)]
+fn mix_columns(q: &mut [u64; 8]) {
+    let [q0, q1, q2, q3, q4, q5, q6, q7] = *q;
+    let [r0, r1, r2, r3, r4, r5, r6, r7] = q.map(|x| x.rotate_right(16));
+    *q = [q7 ^ r7 ^ r0 ^ rotr32(q0 ^ r0),
+          q0 ^ r0 ^ q7 ^ r7 ^ r1 ^ rotr32(q1 ^ r1),
+          q1 ^ r1 ^ r2 ^ rotr32(q2 ^ r2),
+          q2 ^ r2 ^ q7 ^ r7 ^ r3 ^ rotr32(q3 ^ r3),
+          q3 ^ r3 ^ q7 ^ r7 ^ r4 ^ rotr32(q4 ^ r4),
+          q4 ^ r4 ^ r5 ^ rotr32(q5 ^ r5),
+          q5 ^ r5 ^ r6 ^ rotr32(q6 ^ r6),
+          q6 ^ r6 ^ r7 ^ rotr32(q7 ^ r7)];
+}
+
+#[inline(always)]
+fn inv_mix_columns(q: &mut [u64; 8]) {
+    let [q0, q1, q2, q3, q4, q5, q6, q7] = *q;
+    let [r0, r1, r2, r3, r4, r5, r6, r7] = q.map(|x| x.rotate_right(16));
+    *q = [q5 ^ q6 ^ q7 ^ r0 ^ r5 ^ r7 ^ rotr32(q0 ^ q5 ^ q6 ^ r0 ^ r5),
+          q0 ^ q5 ^ r0 ^ r1 ^ r5 ^ r6 ^ r7 ^ rotr32(q1 ^ q5 ^ q7 ^ r1 ^ r5 ^ r6),
+          q0 ^ q1 ^ q6 ^ r1 ^ r2 ^ r6 ^ r7 ^ rotr32(q0 ^ q2 ^ q6 ^ r2 ^ r6 ^ r7),
+          q0 ^ q1 ^ q2 ^ q5 ^ q6 ^ r0 ^ r2 ^ r3 ^ r5 ^ rotr32(q0 ^ q1 ^ q3 ^ q5 ^ q6 ^ q7 ^ r0 ^ r3 ^ r5 ^ r7),
+          q1 ^ q2 ^ q3 ^ q5 ^ r1 ^ r3 ^ r4 ^ r5 ^ r6 ^ r7 ^ rotr32(q1 ^ q2 ^ q4 ^ q5 ^ q7 ^ r1 ^ r4 ^ r5 ^ r6),
+          q2 ^ q3 ^ q4 ^ q6 ^ r2 ^ r4 ^ r5 ^ r6 ^ r7 ^ rotr32(q2 ^ q3 ^ q5 ^ q6 ^ r2 ^ r5 ^ r6 ^ r7),
+          q3 ^ q4 ^ q5 ^ q7 ^ r3 ^ r5 ^ r6 ^ r7 ^ rotr32(q3 ^ q4 ^ q6 ^ q7 ^ r3 ^ r6 ^ r7),
+          q4 ^ q5 ^ q6 ^ r4 ^ r6 ^ r7 ^ rotr32(q4 ^ q5 ^ q7 ^ r4 ^ r7)];
+}
+
+pub struct AES128Bitsliced {
+    keys: BitslicedKeys<11>,
+}
+
+pub struct AES256Bitsliced {
+    keys: BitslicedKeys<15>,
+}
+
+impl fmt::Debug for AES128Bitsliced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AES128Bitsliced").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Debug for AES256Bitsliced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AES256Bitsliced").finish_non_exhaustive()
+    }
+}
+
+impl AES128Bitsliced {
+    pub const NAME: &'static str = "AES-bitsliced";
+
+    // Several blocks per call, in place (4 per bitsliced pass)
+    pub fn encrypt_blocks(&self, blocks: &mut [u128]) {
+        self.keys.encrypt(blocks)
+    }
+
+    pub fn decrypt_blocks(&self, blocks: &mut [u128]) {
+        self.keys.decrypt(blocks)
+    }
+}
+
+impl AES256Bitsliced {
+    pub const NAME: &'static str = "AES256-bitsliced";
+
+    pub fn encrypt_blocks(&self, blocks: &mut [u128]) {
+        self.keys.encrypt(blocks)
+    }
+
+    pub fn decrypt_blocks(&self, blocks: &mut [u128]) {
+        self.keys.decrypt(blocks)
+    }
+}
+
+impl CipherInterface for AES128Bitsliced {
+    type Cipher = AES128Bitsliced;
+
+    fn name(&self) -> &'static str {AES128Bitsliced::NAME}
+    fn level(&self) -> u16 {128}
+
+    fn new(key: &[u128]) -> Self {
+        let mut bytes = key[0].to_be_bytes();
+        let keys = BitslicedKeys::new(&bytes);
+        bytes.zeroize();
+        AES128Bitsliced { keys }
+    }
+
+    fn encrypt_block(&self, input: u128) -> u128 {
+        let mut block = [input];
+        self.keys.encrypt(&mut block);
+        block[0]
+    }
+
+    fn decrypt_block(&self, input: u128) -> u128 {
+        let mut block = [input];
+        self.keys.decrypt(&mut block);
+        block[0]
+    }
+}
+
+impl CipherInterface for AES256Bitsliced {
+    type Cipher = AES256Bitsliced;
+
+    fn name(&self) -> &'static str {AES256Bitsliced::NAME}
+    fn level(&self) -> u16 {256}
+
+    // Same key layout as AES256 : key[0] holds the first 16 bytes
+    fn new(key: &[u128]) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[..16].copy_from_slice(&key[0].to_be_bytes());
+        bytes[16..].copy_from_slice(&key[1].to_be_bytes());
+        let keys = BitslicedKeys::new(&bytes);
+        bytes.zeroize();
+        AES256Bitsliced { keys }
+    }
+
+    fn encrypt_block(&self, input: u128) -> u128 {
+        let mut block = [input];
+        self.keys.encrypt(&mut block);
+        block[0]
+    }
+
+    fn decrypt_block(&self, input: u128) -> u128 {
+        let mut block = [input];
+        self.keys.decrypt(&mut block);
+        block[0]
+    }
+}
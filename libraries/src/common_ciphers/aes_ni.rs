@@ -0,0 +1,111 @@
+use zeroize::Zeroize;
+
+// AES-NI path of AES128 and AES256, taken when the CPU reports AES-NI at run time (the table based code stays the
+// fallback). The round keys come from the table based key expansion : byte k of a round key is XORed with byte k of
+// the block, byte 0 being the most significant byte of the u128.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AesBackend {
+    AesNi,
+    Table,
+}
+
+impl AesBackend {
+    // Fastest backend available on the running CPU
+    pub fn detect() -> Self {
+        if AesBackend::AesNi.is_available() {AesBackend::AesNi} else {AesBackend::Table}
+    }
+
+    pub fn is_available(&self) -> bool {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            AesBackend::AesNi => std::arch::is_x86_feature_detected!("aes"),
+            #[cfg(not(target_arch = "x86_64"))]
+            AesBackend::AesNi => false,
+            AesBackend::Table => true,
+        }
+    }
+}
+
+// Encryption round keys and the matching decryption ones (equivalent inverse cipher : reversed order, InvMixColumns
+// applied to the inner keys), R = number of rounds + 1
+pub struct AesNiKeys<const R: usize> {
+    encryption: [[u8; 16]; R],
+    decryption: [[u8; 16]; R],
+}
+
+impl<const R: usize> Drop for AesNiKeys<R> {
+    fn drop(&mut self) {
+        self.encryption.zeroize();
+        self.decryption.zeroize();
+    }
+}
+
+impl<const R: usize> AesNiKeys<R> {
+    // None when AES-NI is not available, the caller then keeps the table based path
+    pub fn new(round_keys: &[[u8; 16]; R]) -> Option<Self> {
+        if !AesBackend::AesNi.is_available() {return None}
+        let mut keys = AesNiKeys { encryption: *round_keys, decryption: [[0u8; 16]; R] };
+        #[cfg(target_arch = "x86_64")]
+        unsafe { ni::decryption_keys(&keys.encryption, &mut keys.decryption) }
+        Some(keys)
+    }
+
+    #[inline(always)]
+    pub fn encrypt_block(&self, input: u128) -> u128 {
+        #[cfg(target_arch = "x86_64")]
+        return unsafe {ni::encrypt(&self.encryption, input)};
+        #[cfg(not(target_arch = "x86_64"))]
+        unreachable!("AES-NI keys are only built on x86_64")
+    }
+
+    #[inline(always)]
+    pub fn decrypt_block(&self, input: u128) -> u128 {
+        #[cfg(target_arch = "x86_64")]
+        return unsafe {ni::decrypt(&self.decryption, input)};
+        #[cfg(not(target_arch = "x86_64"))]
+        unreachable!("AES-NI keys are only built on x86_64")
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod ni {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "aes")]
+    #[inline]
+    fn load(bytes: &[u8; 16]) -> __m128i {
+        unsafe {_mm_loadu_si128(bytes.as_ptr() as *const __m128i)}
+    }
+
+    #[target_feature(enable = "aes")]
+    #[inline]
+    fn store(x: __m128i) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        unsafe {_mm_storeu_si128(bytes.as_mut_ptr() as *mut __m128i, x)};
+        bytes
+    }
+
+    #[target_feature(enable = "aes")]
+    pub unsafe fn decryption_keys<const R: usize>(encryption: &[[u8; 16]; R], decryption: &mut [[u8; 16]; R]) {
+        decryption[0] = encryption[R - 1];
+        for i in 1..R - 1 {decryption[i] = store(_mm_aesimc_si128(load(&encryption[R - 1 - i])))}
+        decryption[R - 1] = encryption[0];
+    }
+
+    #[target_feature(enable = "aes")]
+    pub unsafe fn encrypt<const R: usize>(keys: &[[u8; 16]; R], input: u128) -> u128 {
+        let mut state = _mm_xor_si128(load(&input.to_be_bytes()), load(&keys[0]));
+        for key in &keys[1..R - 1] {state = _mm_aesenc_si128(state, load(key))}
+        state = _mm_aesenclast_si128(state, load(&keys[R - 1]));
+        u128::from_be_bytes(store(state))
+    }
+
+    #[target_feature(enable = "aes")]
+    pub unsafe fn decrypt<const R: usize>(keys: &[[u8; 16]; R], input: u128) -> u128 {
+        let mut state = _mm_xor_si128(load(&input.to_be_bytes()), load(&keys[0]));
+        for key in &keys[1..R - 1] {state = _mm_aesdec_si128(state, load(key))}
+        state = _mm_aesdeclast_si128(state, load(&keys[R - 1]));
+        u128::from_be_bytes(store(state))
+    }
+}
@@ -7,7 +7,7 @@ use rand::Rng;
 use speck::Speck;
 use xtea::XTEA;
 
-use crate::common_ciphers::{aes256::AES256, aria::Aria, aria256::Aria256, camelia256::Camellia256bit, cast::Cast, cast256::Cast256, rc5::Rc5, rc5256::Rc5256, serpent::Serpent128, xtea256::XTEA256};
+use crate::common_ciphers::{aes256::AES256, aes_bitsliced::{AES128Bitsliced, AES256Bitsliced}, aes_ni::AesBackend, aria::Aria, aria256::Aria256, camelia256::Camellia256bit, cast::Cast, cast256::Cast256, rc5::Rc5, rc5256::Rc5256, serpent::Serpent128, xtea256::XTEA256};
 
 pub trait CipherInterface{
     type Cipher;
@@ -19,6 +19,8 @@ pub trait CipherInterface{
 }
 
 pub mod aes;
+pub mod aes_ni;
+pub mod aes_bitsliced;
 pub mod xtea;
 pub mod camellia;
 pub mod speck;
@@ -34,7 +36,7 @@ pub mod aes256;
 pub mod camelia256;
 pub mod xtea256;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum CipherName {
     XTEA,
     XTEA256,
@@ -53,6 +55,50 @@ pub enum CipherName {
     Rc5256
 }
 
+impl CipherName {
+    // Stable identifier of the PRP, as recorded in ciphertext headers (never renumber existing entries)
+    pub fn id(&self) -> u8 {
+        match self {
+            CipherName::XTEA => 1,
+            CipherName::XTEA256 => 2,
+            CipherName::Speck => 3,
+            CipherName::Lea => 4,
+            CipherName::Camellia => 5,
+            CipherName::Camellia256bit => 6,
+            CipherName::AES128 => 7,
+            CipherName::AES256 => 8,
+            CipherName::Serpent => 9,
+            CipherName::Aria => 10,
+            CipherName::Aria256 => 11,
+            CipherName::Cast => 12,
+            CipherName::Cast256 => 13,
+            CipherName::Rc5 => 14,
+            CipherName::Rc5256 => 15
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(CipherName::XTEA),
+            2 => Some(CipherName::XTEA256),
+            3 => Some(CipherName::Speck),
+            4 => Some(CipherName::Lea),
+            5 => Some(CipherName::Camellia),
+            6 => Some(CipherName::Camellia256bit),
+            7 => Some(CipherName::AES128),
+            8 => Some(CipherName::AES256),
+            9 => Some(CipherName::Serpent),
+            10 => Some(CipherName::Aria),
+            11 => Some(CipherName::Aria256),
+            12 => Some(CipherName::Cast),
+            13 => Some(CipherName::Cast256),
+            14 => Some(CipherName::Rc5),
+            15 => Some(CipherName::Rc5256),
+            _ => None
+        }
+    }
+}
+
 pub enum CommonCipher {
     XTEA(XTEA),
     XTEA256(XTEA256),
@@ -62,6 +108,8 @@ pub enum CommonCipher {
     Camellia256bit(Camellia256bit),
     AES128(AES128),
     AES256(AES256),
+    AES128Bitsliced(AES128Bitsliced),
+    AES256Bitsliced(AES256Bitsliced),
     Serpent128(Serpent128),
     Aria(Aria),
     Aria256(Aria256),
@@ -72,8 +120,12 @@ pub enum CommonCipher {
 }
 
 impl CommonCipher {
+    // Hardened builds never take the table based AES : without AES-NI they use the bitsliced one
     pub fn newcipher(name: &CipherName, key: &[u128]) -> Self {
+        let bitsliced_aes = cfg!(feature = "hardened") && !AesBackend::AesNi.is_available();
         match name {
+            CipherName::AES128 if bitsliced_aes => Self::AES128Bitsliced(AES128Bitsliced::new(key)),
+            CipherName::AES256 if bitsliced_aes => Self::AES256Bitsliced(AES256Bitsliced::new(key)),
             CipherName::XTEA => Self::XTEA(XTEA::new(key)),
             CipherName::XTEA256 => Self::XTEA256(XTEA256::new(key)),
             CipherName::Speck => Self::Speck(Speck::new(key)),
@@ -103,6 +155,8 @@ impl CommonCipher {
             Self::Camellia256bit(c) => c.level(),
             Self::AES128(c) => c.level(),
             Self::AES256(c) => c.level(),
+            Self::AES128Bitsliced(c) => c.level(),
+            Self::AES256Bitsliced(c) => c.level(),
             Self::Serpent128(c) =>c.level(),
             Self::Aria(c) =>c.level(),
             Self::Aria256(c) =>c.level(),
@@ -124,6 +178,8 @@ impl CommonCipher {
             Self::Camellia256bit(c) => c.encrypt_block(input),
             Self::AES128(c) => c.encrypt_block(input),
             Self::AES256(c) => c.encrypt_block(input),
+            Self::AES128Bitsliced(c) => c.encrypt_block(input),
+            Self::AES256Bitsliced(c) => c.encrypt_block(input),
             Self::Serpent128(c) =>c.encrypt_block(input),
             Self::Aria(c) =>c.encrypt_block(input),
             Self::Aria256(c) =>c.encrypt_block(input),
@@ -144,6 +200,8 @@ impl CommonCipher {
             Self::Camellia256bit(c) => c.decrypt_block(input),
             Self::AES128(c) => c.decrypt_block(input),
             Self::AES256(c) => c.decrypt_block(input),
+            Self::AES128Bitsliced(c) => c.decrypt_block(input),
+            Self::AES256Bitsliced(c) => c.decrypt_block(input),
             Self::Serpent128(c)=>c.decrypt_block(input),
             Self::Aria(c)=>c.decrypt_block(input),
             Self::Aria256(c)=>c.decrypt_block(input),
@@ -163,6 +221,8 @@ impl CommonCipher {
             Self::Camellia256bit(_) => "Camellia256",
             Self::AES128(_) => "AES128",
             Self::AES256(_) => "AES256",
+            Self::AES128Bitsliced(_) => AES128Bitsliced::NAME,
+            Self::AES256Bitsliced(_) => AES256Bitsliced::NAME,
             Self::Serpent128(_)=>"Serpent",
             Self::Aria(_)=>"Aria",
             Self::Aria256(_)=>"Aria256",
@@ -172,6 +232,23 @@ impl CommonCipher {
             Self::Rc5256(_)=>"Rc5256"
         }
     }
+
+    // Several blocks in place : the bitsliced AES encrypts them 4 per pass, the other PRPs one by one
+    pub fn encrypt_blocks(&self, blocks: &mut [u128]) {
+        match self {
+            Self::AES128Bitsliced(c) => c.encrypt_blocks(blocks),
+            Self::AES256Bitsliced(c) => c.encrypt_blocks(blocks),
+            _ => for block in blocks.iter_mut() {*block = self.encrypt_block(*block)}
+        }
+    }
+
+    pub fn decrypt_blocks(&self, blocks: &mut [u128]) {
+        match self {
+            Self::AES128Bitsliced(c) => c.decrypt_blocks(blocks),
+            Self::AES256Bitsliced(c) => c.decrypt_blocks(blocks),
+            _ => for block in blocks.iter_mut() {*block = self.decrypt_block(*block)}
+        }
+    }
 }
 
 pub const CIPHER_NAMES: [CipherName; 14] = [
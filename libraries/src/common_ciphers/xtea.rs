@@ -1,9 +1,23 @@
+use std::fmt;
+use zeroize::Zeroize;
 use super::CipherInterface;
 
 pub struct XTEA {
     key: [u32; 4],
 }
 
+impl Drop for XTEA {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl fmt::Debug for XTEA {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XTEA").finish_non_exhaustive()
+    }
+}
+
 impl XTEA {
    pub const NAME: &'static str = "XTEA";
     #[inline]
@@ -1,9 +1,23 @@
+use std::fmt;
+use zeroize::Zeroize;
 use super::CipherInterface;
 
 pub struct XTEA256 {
     key: [u32; 8],
 }
 
+impl Drop for XTEA256 {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl fmt::Debug for XTEA256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XTEA256").finish_non_exhaustive()
+    }
+}
+
 impl XTEA256 {
     pub const NAME: &'static str = "XTEA-256";
     
@@ -0,0 +1,55 @@
+use std::fmt;
+
+// Errors reported by the RSBE library instead of panicking
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RsbeError {
+    InvalidPadding,
+    InvalidLength,
+    Misaligned,
+    InvalidKey,
+    ThresholdTooSmall,
+    ThresholdTooLarge,
+    IndexOutOfRange,
+    MatrixNotInvertible,
+    InconsistentDimensions,
+    ParseError(String),
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    UnknownCipher(u8),
+    KeyIdMismatch,
+    ThreadPoolError(String),
+    AuthenticationFailed,
+    InvalidKdfParameters,
+    InvalidDepth,
+    DivisionByZero,
+    DuplicatePoints,
+}
+
+impl fmt::Display for RsbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RsbeError::InvalidPadding => write!(f, "Invalid padding"),
+            RsbeError::InvalidLength => write!(f, "Invalid data length"),
+            RsbeError::Misaligned => write!(f, "Buffer is not aligned on 16 bytes"),
+            RsbeError::InvalidKey => write!(f, "Invalid key material"),
+            RsbeError::ThresholdTooSmall => write!(f, "Minimal allowed value for the desired threshold is 3"),
+            RsbeError::ThresholdTooLarge => write!(f, "Threshold exceeds the largest supported value"),
+            RsbeError::IndexOutOfRange => write!(f, "Index outside the size of data"),
+            RsbeError::MatrixNotInvertible => write!(f, "Matrix is not invertible"),
+            RsbeError::InconsistentDimensions => write!(f, "All rows must have the same number of columns"),
+            RsbeError::ParseError(s) => write!(f, "Invalid string format in input: {}", s),
+            RsbeError::InvalidHeader => write!(f, "Invalid or truncated ciphertext header"),
+            RsbeError::UnsupportedVersion(v) => write!(f, "Unsupported ciphertext format version {}", v),
+            RsbeError::UnknownCipher(id) => write!(f, "Unknown PRP identifier {}", id),
+            RsbeError::KeyIdMismatch => write!(f, "Ciphertext was produced under another key"),
+            RsbeError::ThreadPoolError(s) => write!(f, "Unable to build the thread pool: {}", s),
+            RsbeError::AuthenticationFailed => write!(f, "Authentication tag does not verify"),
+            RsbeError::InvalidKdfParameters => write!(f, "Invalid key derivation parameters"),
+            RsbeError::InvalidDepth => write!(f, "Recursion depth must be between 1 and 255"),
+            RsbeError::DivisionByZero => write!(f, "Division by the zero polynomial"),
+            RsbeError::DuplicatePoints => write!(f, "Interpolation points must be distinct"),
+        }
+    }
+}
+
+impl std::error::Error for RsbeError {}
@@ -0,0 +1,238 @@
+use std::sync::OnceLock;
+use super::field::GF128;
+
+// Carry-less multiplication backends of GF(2^128) (modulo x^128 + x^7 + x^2 + x + 1, bit i of the u128 being the
+// coefficient of x^i). The pclmul backend is only used when the CPU reports PCLMULQDQ at run time, every other CPU
+// and target uses the portable one. Both give exactly the same results.
+
+// Backend of the running CPU, detected on first use
+static DETECTED: OnceLock<Backend> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Pclmul,
+    Portable,
+}
+
+impl Backend {
+    // Fastest backend available on the running CPU, the CPU features are only queried once
+    #[inline(always)]
+    pub fn detect() -> Self {
+        *DETECTED.get_or_init(|| if Backend::Pclmul.is_available() {Backend::Pclmul} else {Backend::Portable})
+    }
+
+    #[inline(always)]
+    pub fn is_available(&self) -> bool {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Pclmul => std::arch::is_x86_feature_detected!("pclmulqdq") && std::arch::is_x86_feature_detected!("sse4.1"),
+            #[cfg(not(target_arch = "x86_64"))]
+            Backend::Pclmul => false,
+            Backend::Portable => true,
+        }
+    }
+
+    // The products below do not check the CPU again : they must only be called on a backend returned by detect or
+    // checked with is_available, outside the crate the free functions mul, dot and dot_n do so
+
+    // Product of two elements
+    #[inline(always)]
+    pub(crate) fn mul(&self, a: u128, b: u128) -> u128 {
+        #[cfg(target_arch = "x86_64")]
+        if *self == Backend::Pclmul {return unsafe {pclmul::mul(a, b)}}
+        let (lo, hi) = portable::mul_wide(a, b);
+        portable::reduce(lo, hi)
+    }
+
+    // Sum of row[i] * values[i] over the shortest of the two slices, with a single reduction
+    #[inline(always)]
+    pub(crate) fn dot(&self, row: &[GF128], values: &[GF128]) -> GF128 {
+        #[cfg(target_arch = "x86_64")]
+        if *self == Backend::Pclmul {return GF128(unsafe {pclmul::dot(row, values)})}
+        GF128(portable::dot(row, values))
+    }
+
+    // N dot products sharing the same row : values[j][v] is the element j of the vector v. Each row element is
+    // loaded once for the N vectors, the N accumulators are independent and reduced once at the end
+    #[inline(always)]
+    pub(crate) fn dot_n<const N: usize>(&self, row: &[GF128], values: &[[GF128; N]]) -> [GF128; N] {
+        #[cfg(target_arch = "x86_64")]
+        if *self == Backend::Pclmul {return unsafe {pclmul::dot_n(row, values)}.map(GF128)}
+        portable::dot_n(row, values).map(GF128)
+    }
+}
+
+// Product with the backend detected at run time (once, see DETECTED)
+#[inline(always)]
+pub fn mul(a: u128, b: u128) -> u128 {
+    Backend::detect().mul(a, b)
+}
+
+#[inline(always)]
+pub fn dot(row: &[GF128], values: &[GF128]) -> GF128 {
+    Backend::detect().dot(row, values)
+}
+
+#[inline(always)]
+pub fn dot_n<const N: usize>(row: &[GF128], values: &[[GF128; N]]) -> [GF128; N] {
+    Backend::detect().dot_n(row, values)
+}
+
+// Portable backend over u64/u128 integer arithmetic, without table lookups nor data dependent branches
+mod portable {
+    use super::GF128;
+
+    // Low 64 bits of the carry-less product : the operands are split in 4 interleaved classes of bits, so that the
+    // carries of the integer products land in the zero holes between the kept bits and are masked out
+    #[inline(always)]
+    fn bmul64(x: u64, y: u64) -> u64 {
+        const M0: u64 = 0x1111_1111_1111_1111;
+        const M1: u64 = 0x2222_2222_2222_2222;
+        const M2: u64 = 0x4444_4444_4444_4444;
+        const M3: u64 = 0x8888_8888_8888_8888;
+        let (x0, x1, x2, x3) = (x & M0, x & M1, x & M2, x & M3);
+        let (y0, y1, y2, y3) = (y & M0, y & M1, y & M2, y & M3);
+        let z0 = x0.wrapping_mul(y0) ^ x1.wrapping_mul(y3) ^ x2.wrapping_mul(y2) ^ x3.wrapping_mul(y1);
+        let z1 = x0.wrapping_mul(y1) ^ x1.wrapping_mul(y0) ^ x2.wrapping_mul(y3) ^ x3.wrapping_mul(y2);
+        let z2 = x0.wrapping_mul(y2) ^ x1.wrapping_mul(y1) ^ x2.wrapping_mul(y0) ^ x3.wrapping_mul(y3);
+        let z3 = x0.wrapping_mul(y3) ^ x1.wrapping_mul(y2) ^ x2.wrapping_mul(y1) ^ x3.wrapping_mul(y0);
+        (z0 & M0) | (z1 & M1) | (z2 & M2) | (z3 & M3)
+    }
+
+    // Full 64 x 64 -> 128 carry-less product, the high half coming from the product of the bit-reversed operands
+    #[inline(always)]
+    fn clmul64(x: u64, y: u64) -> u128 {
+        let lo = bmul64(x, y);
+        let hi = bmul64(x.reverse_bits(), y.reverse_bits()).reverse_bits() >> 1;
+        (hi as u128) << 64 | lo as u128
+    }
+
+    // 128 x 128 -> 256 carry-less product (Karatsuba, 3 products), returned as (low, high) halves
+    #[inline(always)]
+    pub fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+        let (a0, a1) = (a as u64, (a >> 64) as u64);
+        let (b0, b1) = (b as u64, (b >> 64) as u64);
+        let lo = clmul64(a0, b0);
+        let hi = clmul64(a1, b1);
+        let mid = clmul64(a0 ^ a1, b0 ^ b1) ^ lo ^ hi;
+        (lo ^ (mid << 64), hi ^ (mid >> 64))
+    }
+
+    // Reduction modulo x^128 + x^7 + x^2 + x + 1 : x^128 = x^7 + x^2 + x + 1, applied twice for the bits that the
+    // first fold pushes above x^127
+    #[inline(always)]
+    pub fn reduce(lo: u128, hi: u128) -> u128 {
+        let overflow = (hi >> 127) ^ (hi >> 126) ^ (hi >> 121);
+        let hi = hi ^ overflow;
+        lo ^ hi ^ (hi << 1) ^ (hi << 2) ^ (hi << 7)
+    }
+
+    pub fn dot(row: &[GF128], values: &[GF128]) -> u128 {
+        let (mut acc_lo, mut acc_hi) = (0u128, 0u128);
+        for (m, v) in row.iter().zip(values) {
+            let (lo, hi) = mul_wide(m.0, v.0);
+            acc_lo ^= lo;
+            acc_hi ^= hi;
+        }
+        reduce(acc_lo, acc_hi)
+    }
+
+    pub fn dot_n<const N: usize>(row: &[GF128], values: &[[GF128; N]]) -> [u128; N] {
+        let (mut acc_lo, mut acc_hi) = ([0u128; N], [0u128; N]);
+        for (m, vs) in row.iter().zip(values) {
+            for v in 0..N {
+                let (lo, hi) = mul_wide(m.0, vs[v].0);
+                acc_lo[v] ^= lo;
+                acc_hi[v] ^= hi;
+            }
+        }
+        std::array::from_fn(|v| reduce(acc_lo[v], acc_hi[v]))
+    }
+}
+
+// PCLMULQDQ (and SSE4.1) backend, only called after a successful run-time detection
+#[cfg(target_arch = "x86_64")]
+mod pclmul {
+    use std::arch::x86_64::*;
+    use super::GF128;
+
+    #[target_feature(enable = "pclmulqdq,sse4.1")]
+    #[inline]
+    fn load(x: &u128) -> __m128i {
+        unsafe {_mm_loadu_si128(x as *const u128 as *const __m128i)}
+    }
+
+    #[target_feature(enable = "pclmulqdq,sse4.1")]
+    #[inline]
+    fn store(x: __m128i) -> u128 {
+        let mut result = 0u128;
+        unsafe {_mm_storeu_si128(&mut result as *mut u128 as *mut __m128i, x)};
+        result
+    }
+
+    // 128 x 128 -> 256 carry-less product as (low, high) halves
+    #[target_feature(enable = "pclmulqdq,sse4.1")]
+    #[inline]
+    fn mul_wide(a: __m128i, b: __m128i) -> (__m128i, __m128i) {
+        let h0 = _mm_clmulepi64_si128(a, b, 0x00);  // a_low * b_low
+        let h1 = _mm_clmulepi64_si128(a, b, 0x01);  // a_low * b_high
+        let h2 = _mm_clmulepi64_si128(a, b, 0x10);  // a_high * b_low
+        let h3 = _mm_clmulepi64_si128(a, b, 0x11);  // a_high * b_high
+        let h1h2 = _mm_xor_si128(h1, h2);
+        (_mm_xor_si128(h0, _mm_slli_si128(h1h2, 8)), _mm_xor_si128(h3, _mm_srli_si128(h1h2, 8)))
+    }
+
+    // Reduce a 256-bit value modulo x^128 + x^7 + x^2 + x + 1 (bit pattern 0x87 for x^7 + x^2 + x + 1)
+    #[target_feature(enable = "pclmulqdq,sse4.1")]
+    #[inline]
+    fn reduce(lo: __m128i, hi: __m128i) -> __m128i {
+        let poly = _mm_set_epi64x(0, 0x87);
+        let t0 = _mm_clmulepi64_si128(hi, poly, 0x00);
+        let t1 = _mm_clmulepi64_si128(hi, poly, 0x01);
+        let v0 = _mm_xor_si128(lo, t0);
+        let v1 = _mm_xor_si128(v0, _mm_slli_si128(t1, 8));
+        let t2 = _mm_srli_si128(t1, 8);
+        let t3 = _mm_clmulepi64_si128(t2, poly, 0x00);
+        _mm_xor_si128(v1, t3)
+    }
+
+    #[target_feature(enable = "pclmulqdq,sse4.1")]
+    pub unsafe fn mul(a: u128, b: u128) -> u128 {
+        let (lo, hi) = mul_wide(load(&a), load(&b));
+        store(reduce(lo, hi))
+    }
+
+    #[target_feature(enable = "pclmulqdq,sse4.1")]
+    pub unsafe fn dot(row: &[GF128], values: &[GF128]) -> u128 {
+        let mut acc_lo = _mm_setzero_si128();
+        let mut acc_hi = _mm_setzero_si128();
+        for (m, v) in row.iter().zip(values) {
+            let (lo, hi) = mul_wide(load(&m.0), load(&v.0));
+            acc_lo = _mm_xor_si128(acc_lo, lo);
+            acc_hi = _mm_xor_si128(acc_hi, hi);
+        }
+        store(reduce(acc_lo, acc_hi))
+    }
+
+    // Karatsuba is not worth it here : the middle products are accumulated apart and only folded into the low and
+    // high halves before the reduction, so each product costs 4 clmul and 4 xor
+    #[target_feature(enable = "pclmulqdq,sse4.1")]
+    pub unsafe fn dot_n<const N: usize>(row: &[GF128], values: &[[GF128; N]]) -> [u128; N] {
+        let mut acc_lo = [_mm_setzero_si128(); N];
+        let mut acc_mid = [_mm_setzero_si128(); N];
+        let mut acc_hi = [_mm_setzero_si128(); N];
+        for (m, vs) in row.iter().zip(values) {
+            let a = load(&m.0);
+            for v in 0..N {
+                let b = load(&vs[v].0);
+                acc_lo[v] = _mm_xor_si128(acc_lo[v], _mm_clmulepi64_si128(a, b, 0x00));
+                acc_mid[v] = _mm_xor_si128(acc_mid[v], _mm_xor_si128(_mm_clmulepi64_si128(a, b, 0x01), _mm_clmulepi64_si128(a, b, 0x10)));
+                acc_hi[v] = _mm_xor_si128(acc_hi[v], _mm_clmulepi64_si128(a, b, 0x11));
+            }
+        }
+        std::array::from_fn(|v| {   let lo = _mm_xor_si128(acc_lo[v], _mm_slli_si128(acc_mid[v], 8));
+                                    let hi = _mm_xor_si128(acc_hi[v], _mm_srli_si128(acc_mid[v], 8));
+                                    store(reduce(lo, hi))
+                                })
+    }
+}
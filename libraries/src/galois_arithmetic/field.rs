@@ -1,85 +1,46 @@
-use core::arch::x86_64::{__m128i, _mm_clmulepi64_si128, _mm_xor_si128, _mm_slli_si128};
-use std::{  arch::x86_64::{_mm_cmpeq_epi8, _mm_extract_epi64, _mm_loadu_si128, _mm_movemask_epi8, _mm_set_epi64x, _mm_setzero_si128, _mm_srli_si128}, 
-            fmt, ops::{Add, AddAssign, BitXor, Div, Mul, Sub}, str::FromStr};
+use std::{fmt, ops::{Add, AddAssign, BitXor, Div, Mul, Sub}, str::FromStr};
 use rand::Rng;
+use smallvec::SmallVec;
+use zeroize::Zeroize;
 use crate::common_ciphers::CommonCipher;
+use super::backend;
 
+// Inline capacity of vectors and matrix rows, larger sizes (thresholds above 15) go to the heap
 pub const MAX_VECTOR_ELEMENTS :usize = 30;
 
-#[derive(Copy, Clone, Debug)]
-pub struct GF128(pub __m128i);
+// Bit i is the coefficient of x^i, the products go through the carry-less multiply backend detected at run time
+#[derive(Copy, Clone)]
+#[repr(align(16))]
+pub struct GF128(pub u128);
 
-#[inline(always)] 
-fn u128_to_m128(x: u128) -> __m128i {
-        let lo = x as u64;
-        let hi = (x >> 64) as u64;
-        unsafe { _mm_set_epi64x(hi as i64, lo as i64) }
+// a^(2^n) with n squarings
+#[inline(always)]
+fn gf_sqr_n(backend: backend::Backend, mut a: u128, n: usize) -> u128 {
+    for _ in 0..n {a = backend.mul(a, a)}
+    a
 }
 
-#[inline(always)] 
-fn m128_to_u128(x: __m128i) -> u128 {
-    unsafe {    let lo = _mm_extract_epi64(x, 0) as u64;
-                let hi = _mm_extract_epi64(x, 1) as u64;
-                (hi as u128) << 64 | lo as u128
-           }
-}
-
-// Multiply two 128-bit field elements in GF(2^128) #[target_feature(enable = "pclmulqdq")]
-#[inline(always)] 
-fn gf_mul(a: __m128i, b: __m128i) -> __m128i {   
-    unsafe {    let h0 = _mm_clmulepi64_si128(a, b, 0x00);  // a_low * b_low
-                let h1 = _mm_clmulepi64_si128(a, b, 0x01);  // a_low * b_high  
-                let h2 = _mm_clmulepi64_si128(a, b, 0x10);  // a_high * b_low
-                let h3 = _mm_clmulepi64_si128(a, b, 0x11);  // a_high * b_high
-                let h1h2 = _mm_xor_si128(h1, h2);   
-                let lo = _mm_xor_si128(h0, _mm_slli_si128(h1h2, 8));
-                let hi = _mm_xor_si128(h3, _mm_srli_si128(h1h2, 8));
-
-                // Reduce a 256-bit value modulo x^128 + x^7 + x^2 + x + 1
-                // The polynomial can be represented as the bit pattern 10000111 = 0x87 when we consider x^128 ≡ x^7 + x^2 + x + 1
-                let poly = _mm_set_epi64x(0, 0x87);       
-                let t0 = _mm_clmulepi64_si128(hi, poly, 0x00);
-                let t1 = _mm_clmulepi64_si128(hi, poly, 0x01);
-                let v0 = _mm_xor_si128(lo, t0);
-                let v1 = _mm_xor_si128(v0, _mm_slli_si128(t1, 8));        
-                let t2 = _mm_srli_si128(t1, 8);
-                let t3 = _mm_clmulepi64_si128(t2, poly, 0x00);        
-                _mm_xor_si128(v1, t3)
-            }
-}
-
-#[inline(always)] 
-fn gf_inv(a: __m128i) -> __m128i {
-    unsafe {
-        let zero = _mm_setzero_si128();
-        if _mm_extract_epi64(a, 0) == 0 && _mm_extract_epi64(a, 1) == 0 {return zero;}
-        // Precompute odd powers for 4-bit windows: a^1, a^3, a^5, a^7, a^9, a^11, a^13, a^15
-        let mut table = [_mm_setzero_si128(); 16];
-        table[1] = a;  
-        let a_squared = gf_mul(a, a);  // a^2        
-        for i in (3..16).step_by(2) {table[i] = gf_mul(table[i - 2], a_squared);}
-        let mut even_table = [_mm_setzero_si128(); 16];
-        even_table[2] = a_squared;
-        for i in (4..16).step_by(2) {even_table[i] = gf_mul(even_table[i - 2], a_squared);}
-        let exponent: u128 = 0xFFFFFFFF_FFFFFFFF_FFFFFFFF_FFFFFFFE;
-        let mut result = u128_to_m128(1);
-        let mut bit_pos = 128;
-        while bit_pos >= 4 {    bit_pos -= 4;
-                                let window_value = ((exponent >> bit_pos) & 0xF) as usize;
-                                result = gf_mul(result, result);
-                                result = gf_mul(result, result);
-                                result = gf_mul(result, result);
-                                result = gf_mul(result, result);
-                                if window_value != 0 {
-                                    if (window_value & 1) == 1 {
-                                        result = gf_mul(result, table[window_value]);
-                                    } else {
-                                        result = gf_mul(result, even_table[window_value]);
-                                    }
-                                }
-                            }
-        result
-    }   
+// Inverse by Fermat, a^-1 = a^(2^128 - 2) = (a^(2^127 - 1))^2, with the Itoh-Tsujii addition chain on the exponents
+// 2^k - 1 : a^(2^(i+j) - 1) = (a^(2^i - 1))^(2^j) * a^(2^j - 1). The sequence of operations is fixed whatever the
+// input (127 squarings and 12 products, no branch nor table lookup) and 0 maps to 0
+#[inline(always)]
+fn gf_inv(a: u128) -> u128 {
+    let backend = backend::Backend::detect();
+    let chain = |t_i: u128, t_j: u128, j: usize| backend.mul(gf_sqr_n(backend, t_i, j), t_j);
+    let t1 = a;
+    let t2 = chain(t1, t1, 1);
+    let t4 = chain(t2, t2, 2);
+    let t8 = chain(t4, t4, 4);
+    let t16 = chain(t8, t8, 8);
+    let t32 = chain(t16, t16, 16);
+    let t64 = chain(t32, t32, 32);
+    let t96 = chain(t64, t32, 32);
+    let t112 = chain(t96, t16, 16);
+    let t120 = chain(t112, t8, 8);
+    let t124 = chain(t120, t4, 4);
+    let t126 = chain(t124, t2, 2);
+    let t127 = chain(t126, t1, 1);
+    gf_sqr_n(backend, t127, 1)
 }
 
 
@@ -96,42 +57,82 @@ impl GF128 {
             self.to_u128() == 1
         }
     
+    // All ones when the element is zero, 0 otherwise, without a branch
+    #[inline(always)] 
+    pub fn zero_mask(&self) -> u128
+        {
+            ((self.0 | self.0.wrapping_neg()) >> 127).wrapping_sub(1)
+        }
+
+    // other when mask is all ones, self when mask is 0, without a branch
+    #[inline(always)] 
+    pub fn select(&self, other: &GF128, mask: u128) -> Self
+        {
+            GF128(self.0 ^ ((self.0 ^ other.0) & mask))
+        }
+    
     #[inline(always)] 
         pub fn to_u128(&self)-> u128           // Convert to u128
         {
-            m128_to_u128(self.0)   
+            self.0
         }
     
     #[inline(always)] 
     pub fn random() -> Self
         {                                           // Generate a random element
-            GF128(u128_to_m128(rand::rng().random::<u128>()))
+            GF128(rand::rng().random::<u128>())
         }
     
     #[inline(always)] 
     pub fn addto(&self, rhs: &GF128) -> Self 
         {                            // Add two Gf128 element 
-            unsafe {GF128(_mm_xor_si128(self.0 , rhs.0))}
+            GF128(self.0 ^ rhs.0)
         }
     
     #[inline(always)] 
     pub fn subtract(&self, rhs: &GF128) -> Self 
         {                            // Substract two Gf128 element (same as Add)
-            unsafe {GF128(_mm_xor_si128(self.0 , rhs.0))}
+            GF128(self.0 ^ rhs.0)
         }
 
     #[inline(always)] 
     pub fn multiply(&self, rhs: &GF128) -> Self 
         {                       // Multiply two Gf128 element 
-            GF128(gf_mul(self.0, rhs.0))
+            GF128(backend::mul(self.0, rhs.0))
         }
     
     #[inline(always)] 
     pub fn invert(&self) -> Self 
-        {                                       // Inverte a Gf128 element 
+        {                                       // Inverte a Gf128 element in constant time (0 gives 0)
             GF128(gf_inv(self.0))
         }
 
+    // Inverses of all the elements in place with Montgomery's trick : one inversion of the product of the elements,
+    // then 3(n-1) products. The zero elements are replaced by 1 under a mask and give 0 back, as with invert, without
+    // any branch on the values
+    pub fn batch_invert(elements: &mut [GF128])
+        {
+            let Some(first) = elements.first() else {return};
+            let one = GF128::from(1);
+            let mut prefix: SmallVec<[GF128; MAX_VECTOR_ELEMENTS]> = SmallVec::with_capacity(elements.len());
+            prefix.push(first.select(&one, first.zero_mask()));
+            for i in 1..elements.len() {
+                let product = prefix[i - 1] * elements[i].select(&one, elements[i].zero_mask());
+                prefix.push(product);
+            }
+            // inverse holds the inverse of the product of the elements 0..=i
+            let mut inverse = prefix[elements.len() - 1].invert();
+            for i in (1..elements.len()).rev() {
+                let mask = elements[i].zero_mask();
+                let result = inverse * prefix[i - 1];
+                inverse = inverse * elements[i].select(&one, mask);
+                elements[i] = result.select(&GF128::from(0), mask);
+            }
+            elements[0] = inverse.select(&GF128::from(0), elements[0].zero_mask());
+            prefix.iter_mut().for_each(|e| e.zeroize());
+            inverse.zeroize();
+        }
+
     #[inline(always)] 
     pub fn divide(&self, rhs: &GF128) -> Self 
         {                          // Divide two Gf128 element 
@@ -139,12 +140,13 @@ impl GF128 {
             self.multiply(&invc) 
         }
 
+    // Square and multiply for public exponents : the branches follow the exponent bits (not the element), secret
+    // exponents go through pow_ct. The cipher only raises the mask factors to vector indices, which are public
     #[inline(always)] 
     pub fn pow(&self, exponent: usize) -> Self 
         {
             if exponent == 0 { return GF128::from(1u128); }
             if exponent == 1 { return *self;     }
-            if self.is_zero() {  return GF128::from(0u128);  }
             let mut result = GF128::from(1u128); 
             let mut base = self.clone();       
             let mut exp = exponent;    
@@ -156,21 +158,41 @@ impl GF128 {
             result
         }
 
+    // Montgomery ladder over the 128 bits of a secret exponent : the same squaring and product for every bit, the
+    // registers being swapped by masks
+    pub fn pow_ct(&self, exponent: u128) -> Self 
+        {
+            let backend = backend::Backend::detect();
+            let (mut r0, mut r1) = (1u128, self.0);
+            for i in (0..128).rev() {
+                let mask = 0u128.wrapping_sub((exponent >> i) & 1);
+                let swap = (r0 ^ r1) & mask;
+                r0 ^= swap;
+                r1 ^= swap;
+                r1 = backend.mul(r0, r1);
+                r0 = backend.mul(r0, r0);
+                let swap = (r0 ^ r1) & mask;
+                r0 ^= swap;
+                r1 ^= swap;
+            }
+            GF128(r0)
+        }
+
     pub fn to_bytes(&self) ->[u8;16]
         {
-            m128_to_u128(self.0).to_be_bytes()
+            self.0.to_be_bytes()
         }
 
     #[inline(always)] 
     pub fn prp_encrypt(&self, prp :&CommonCipher) -> GF128 
         {
-            GF128(u128_to_m128(prp.encrypt_block(self.to_u128())))
+            GF128(prp.encrypt_block(self.to_u128()))
         }
 
     #[inline(always)] 
     pub fn prp_decrypt(&self, prp :&CommonCipher) -> GF128 
         {
-            GF128(u128_to_m128(prp.decrypt_block(self.to_u128())))
+            GF128(prp.decrypt_block(self.to_u128()))
         }
     
 }
@@ -178,14 +200,14 @@ impl GF128 {
 impl From<u128> for GF128 
     {
         fn from(input: u128) -> Self {
-            GF128(u128_to_m128(input))
+            GF128(input)
         }
     }
 
 impl From<&[u8; 16]> for GF128 
     {
         fn from(bytes: &[u8; 16]) -> Self {
-            GF128(u128_to_m128(u128::from_be_bytes(*bytes)))
+            GF128(u128::from_be_bytes(*bytes))
         }
     }
 
@@ -195,7 +217,7 @@ impl From<&[u64; 2]> for GF128
             let mut buf = [0u8; 16];
             buf[0..8].copy_from_slice(&qwords[0].to_be_bytes());
             buf[8..16].copy_from_slice(&qwords[1].to_be_bytes());
-            GF128(u128_to_m128(u128::from_be_bytes(buf)))
+            GF128(u128::from_be_bytes(buf))
         }
     }
 
@@ -208,7 +230,7 @@ impl From<&[u16; 8]> for GF128
                 buf[2 * i] = bytes[0];
                 buf[2 * i + 1] = bytes[1];
             }
-            GF128(u128_to_m128(u128::from_be_bytes(buf)))
+            GF128(u128::from_be_bytes(buf))
         }
     }
 
@@ -220,7 +242,7 @@ impl From<&[u32; 4]> for GF128
                 let bytes = dword.to_be_bytes();
                 buf[4 * i..4 * (i + 1)].copy_from_slice(&bytes);
             }
-            GF128(u128_to_m128(u128::from_be_bytes(buf)))
+            GF128(u128::from_be_bytes(buf))
         }
     }
 
@@ -242,10 +264,22 @@ impl FromStr for GF128
                 u128::from_str(s)
                     .map_err(|_| "Invalid decimal string")?
             };        
-            unsafe {
-                let m128i = _mm_loadu_si128(&value as *const u128 as *const __m128i);
-                Ok(GF128(m128i))
-            }
+            Ok(GF128(value))
+        }
+    }
+
+// Elements are mostly key material or masks, Debug never shows them (use Display or LowerHex explicitly)
+impl fmt::Debug for GF128 
+    {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "GF128(..)")
+        }
+    }
+
+impl Zeroize for GF128 
+    {
+        fn zeroize(&mut self) {
+            self.0.zeroize()
         }
     }
 
@@ -286,9 +320,7 @@ impl AddAssign for GF128
         #[inline(always)] 
         fn add_assign(&mut self, other: Self) {
             // In GF(2^128), addition is XOR
-        unsafe {
-                self.0 = _mm_xor_si128(self.0, other.0);
-            }
+            *self = self.addto(&other);
         }
     }
 
@@ -296,9 +328,7 @@ impl AddAssign<&GF128> for GF128
     {
         #[inline(always)] 
         fn add_assign(&mut self, other: &Self) {
-            unsafe {
-                self.0 = _mm_xor_si128(self.0, other.0);
-            }
+                *self = self.addto(other);
         }
     }
 
@@ -343,10 +373,7 @@ impl PartialEq for GF128
     {
         #[inline(always)] 
         fn eq(&self, other: &Self) -> bool {        
-        unsafe {
-                let cmp = _mm_cmpeq_epi8(self.0, other.0);
-                _mm_movemask_epi8(cmp) == 0xFFFF
-            }    
+            self.0 == other.0
         }
     }
 
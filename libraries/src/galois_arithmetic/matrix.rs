@@ -1,8 +1,9 @@
 use std::fmt;
 use smallvec::SmallVec;
-use crate::galois_arithmetic::field::MAX_VECTOR_ELEMENTS;
+use zeroize::Zeroize;
+use crate::{error::RsbeError, galois_arithmetic::field::MAX_VECTOR_ELEMENTS};
 
-use super::{field::GF128, vector::GF128Vector};
+use super::{backend, field::GF128, poly::GF128Poly, vector::GF128Vector};
 
 // Using SmallVec with inline capacity of MAX_VECTOR_ELEMENTS elements
 type MatrixRow = SmallVec<[GF128; MAX_VECTOR_ELEMENTS]>;
@@ -14,6 +15,190 @@ pub struct GF128Matrix {
     true_size: usize,
 }
 
+// Inverses of the leading (k x k) sub-matrices of a Vandermonde matrix, computed by Sherman-Morrison-Woodbury
+// bordering : each step from k to k+1 costs O(k^2), so reaching size n costs the same as inverting it once and the
+// bordering can be continued later from where it stopped. All the sizes up to PACKED_SIZE are kept, packed row-major
+// one after the other. Above it, keeping every size would need memory in n^3 : only the requested sizes and the
+// frontier of the bordering are kept
+#[derive(Clone, Debug, Default)]
+pub struct VandermondeInverses {
+    data: Vec<GF128>,                   // Sizes 1..=min(max_size, PACKED_SIZE)
+    kept: Vec<(usize, Vec<GF128>)>,     // Requested sizes above PACKED_SIZE
+    frontier: Vec<GF128>,               // Inverse of size max_size when above PACKED_SIZE
+    max_size: usize,
+}
+
+pub const PACKED_SIZE: usize = MAX_VECTOR_ELEMENTS;
+
+impl VandermondeInverses {
+    // Position of the (size x size) inverse : sum of j^2 for j < size
+    #[inline(always)]
+    fn offset(size: usize) -> usize {
+        (size - 1) * size * (2 * size - 1) / 6
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    // Row-major (size x size) inverse
+    #[inline(always)]
+    pub fn get(&self, size: usize) -> Option<&[GF128]> {
+        if size == 0 {return None}
+        if size <= PACKED_SIZE {
+            if size > self.max_size {return None}
+            let start = Self::offset(size);
+            return Some(&self.data[start..start + size * size])
+        }
+        if size == self.max_size {return Some(&self.frontier)}
+        self.kept.iter().find(|(kept_size, _)| *kept_size == size).map(|(_, inverse)| inverse.as_slice())
+    }
+
+    // Continue the bordering of `matrix` (the one the previous inverses come from) up to (new_max x new_max)
+    pub fn extend(&mut self, matrix: &GF128Matrix, new_max: usize) -> Result<(), RsbeError> {
+        self.require(matrix, &[new_max])
+    }
+
+    // Make the inverses of the given sizes available. Sizes above PACKED_SIZE that were passed by the bordering
+    // without being kept make it start again from PACKED_SIZE
+    pub fn require(&mut self, matrix: &GF128Matrix, sizes: &[usize]) -> Result<(), RsbeError> {
+        let target = sizes.iter().copied().max().unwrap_or(0);
+        if target > matrix.true_size {return Err(RsbeError::InconsistentDimensions)}
+        if sizes.iter().all(|&size| size == 0 || self.get(size).is_some()) {return Ok(())}
+        if self.max_size > PACKED_SIZE && sizes.iter().any(|&size| size < self.max_size && self.get(size).is_none()) {
+            self.frontier.zeroize();
+            self.max_size = PACKED_SIZE;
+        }
+        if self.max_size == 0 {     // Base case: 1x1 matrix [1] has inverse [1]
+            self.data.push(GF128::from(1));
+            self.max_size = 1;
+        }
+        if target <= PACKED_SIZE {self.data.reserve(Self::offset(target + 1).saturating_sub(self.data.len()))}
+        let mut singular = 0u128;
+        while self.max_size < target {
+            let k = self.max_size;
+            let (next, zero_mask) = if k <= PACKED_SIZE {Self::border(&self.data[Self::offset(k)..], matrix, k)}
+                                    else {Self::border(&self.frontier, matrix, k)};
+            singular |= zero_mask;
+            if k < PACKED_SIZE {self.data.extend_from_slice(&next)}
+            else {  let mut previous = std::mem::replace(&mut self.frontier, next);
+                    if k > PACKED_SIZE && sizes.contains(&k) && !self.kept.iter().any(|(size, _)| *size == k) {self.kept.push((k, previous))}
+                    else {previous.zeroize()}
+                }
+            self.max_size += 1;
+        }
+        // Checked once the bordering is done rather than at the first zero Schur complement, the inverses past it are
+        // meaningless and all of them are dropped
+        if singular != 0 {self.zeroize(); return Err(RsbeError::MatrixNotInvertible)}
+        Ok(())
+    }
+
+    // Inverse of size k+1 from the one of size k (previous is kxk, row k and column k are added), with the zero mask of
+    // the Schur complement (all ones when the matrix of size k+1 is singular)
+    fn border(prev: &[GF128], matrix: &GF128Matrix, k: usize) -> (Vec<GF128>, u128) {
+        let mut ainv_u = SmallVec::<[GF128; MAX_VECTOR_ELEMENTS]>::from_elem(GF128::from(0), k);
+        let mut vt_ainv = SmallVec::<[GF128; MAX_VECTOR_ELEMENTS]>::from_elem(GF128::from(0), k);
+        // Compute A^{-1} * u (where u is new column) and v^T * A^{-1} (where v^T is new row)
+        for i in 0..k {
+            for j in 0..k {
+                ainv_u[i] += prev[i * k + j] * matrix.data[j][k];
+                vt_ainv[j] += matrix.data[k][i] * prev[i * k + j];
+            }
+        }
+        // Compute the Schur complement: d - v^T * A^{-1} * u
+        let mut vt_ainv_u = GF128::from(0);
+        for i in 0..k {vt_ainv_u += vt_ainv[i] * matrix.data[i][k]}
+        let schur_complement = matrix.data[k][k] + vt_ainv_u; // GF: subtraction = addition
+        let gamma = schur_complement.invert();
+        let mut next = Vec::with_capacity((k + 1) * (k + 1));
+        // Top blocks: A^{-1} + γ * (A^{-1} * u) * (v^T * A^{-1}) and γ * A^{-1} * u
+        for i in 0..k {
+            for j in 0..k {next.push(prev[i * k + j] + gamma * ainv_u[i] * vt_ainv[j])}
+            next.push(gamma * ainv_u[i]);
+        }
+        // Bottom blocks: γ * v^T * A^{-1} and γ
+        for value in vt_ainv.iter() {next.push(gamma * *value)}
+        next.push(gamma);
+        (next, schur_complement.zero_mask())
+    }
+}
+
+impl Zeroize for VandermondeInverses {
+    fn zeroize(&mut self) {
+        self.data.zeroize();
+        self.kept.iter_mut().for_each(|(_, inverse)| inverse.zeroize());
+        self.kept.clear();
+        self.frontier.zeroize();
+        self.max_size = 0;
+    }
+}
+
+// Solver of the Vandermonde systems sum_j x_i^j * c_j = y_i (i, j < n), without any inverse matrix : the solution is
+// the coefficients of the polynomial interpolating the y_i at the nodes x_i, in the closed Lagrange form
+// sum_i y_i * w_i * M(X) / (X - x_i), with M the product of the (X - x_i) and w_i = 1 / M'(x_i). Only the nodes, M and
+// the weights are kept (O(n) storage, built in O(n^2) with a single inversion). Writing g_i = y_i * w_i and
+// s_p = sum_i g_i * x_i^p, coefficient k is sum_{l > k} m_l * s_{l-k-1} : a solve costs n^2 products for the power
+// sums and n dot products
+#[derive(Clone, Debug, Default)]
+pub struct VandermondeSolver {
+    nodes: Vec<GF128>,
+    master: Vec<GF128>,     // Coefficients of M, monic of degree n
+    weights: Vec<GF128>,
+}
+
+impl VandermondeSolver {
+    // The nodes must be distinct (MatrixNotInvertible otherwise)
+    pub fn new(nodes: &[GF128]) -> Result<Self, RsbeError> {
+        let master = GF128Poly::from_roots(nodes);
+        let mut weights = master.derivative().evaluate_many(nodes);
+        // Every weight is looked at, only whether one of them is zero comes out
+        if weights.iter().fold(0u128, |mask, w| mask | w.zero_mask()) != 0 {return Err(RsbeError::MatrixNotInvertible)}
+        GF128::batch_invert(&mut weights);
+        Ok(VandermondeSolver { nodes: nodes.to_vec(), master: master.coefficients().to_vec(), weights })
+    }
+
+    // Solver of the leading (size x size) sub-matrix of a Vandermonde matrix, whose nodes are its second column
+    pub fn from_vandermonde(matrix: &GF128Matrix, size: usize) -> Result<Self, RsbeError> {
+        if size > matrix.true_size || matrix.true_size < 2 {return Err(RsbeError::InconsistentDimensions)}
+        let nodes: SmallVec<[GF128; MAX_VECTOR_ELEMENTS]> = (0..size).map(|i| matrix.data[i][1]).collect();
+        Self::new(&nodes)
+    }
+
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // Solve in place : `values` holds the y_i and receives the c_j. `scratch` must hold at least size() elements
+    #[inline(always)]
+    pub fn solve(&self, values: &mut [GF128], scratch: &mut [GF128]) {
+        let n = self.nodes.len();
+        let (values, power_sums) = (&mut values[..n], &mut scratch[..n]);
+        for (value, weight) in values.iter_mut().zip(&self.weights) {*value = *value * *weight}
+        for sum in power_sums.iter_mut() {
+            *sum = GF128::from(0);
+            for (value, node) in values.iter_mut().zip(&self.nodes) {
+                *sum += *value;
+                *value = *value * *node;
+            }
+        }
+        for (k, value) in values.iter_mut().enumerate() {*value = backend::dot(&self.master[k + 1..], power_sums)}
+    }
+}
+
+impl Zeroize for VandermondeSolver {
+    fn zeroize(&mut self) {
+        self.nodes.zeroize();
+        self.master.zeroize();
+        self.weights.zeroize();
+    }
+}
+
+impl Zeroize for GF128Matrix {
+    fn zeroize(&mut self) {
+        self.data.iter_mut().flat_map(|row| row.iter_mut()).for_each(|e| e.zeroize());
+    }
+}
+
 
 impl GF128Matrix {
     pub fn new(size: usize) -> Self {
@@ -57,27 +242,23 @@ impl GF128Matrix {
         Self {  data: sub_data, true_size: k}
     }
 
-    // Matrix Inversion using Gauss-jordan elimination algorithm
+    // Matrix Inversion using Gauss-jordan elimination algorithm, in constant time : instead of searching and swapping
+    // a non zero pivot, every row below is added to row i under a mask that stays set while the pivot is zero. The
+    // singularity is only checked at the end
     #[inline(always)]
     pub fn invert(&self) -> Option<GF128Matrix> {
         let n = self.true_size;
         let mut a = self.data.clone();
         let mut inv = GF128Matrix::identity(n).data;
+        let mut singular = 0u128;
         for i in 0..n {
-            if a[i][i].is_zero() {
-                let mut found = false;
-                for j in (i + 1)..n {
-                    if !a[j][i].is_zero() {
-                        a.swap(i, j);
-                        inv.swap(i, j);
-                        found = true;
-                        break;
-                    }
-                }
-                if !found {
-                    return None;
-                }
+            for j in (i + 1)..n {
+                let mask = a[i][i].zero_mask() & !a[j][i].zero_mask();
+                for k in 0..n {  a[i][k] = a[i][k].select(&(a[i][k] + a[j][k]), mask);
+                                        inv[i][k] = inv[i][k].select(&(inv[i][k] + inv[j][k]), mask);
+                                    }
             }
+            singular |= a[i][i].zero_mask();
             let inv_pivot = a[i][i].invert();
             for k in 0..n {  a[i][k] = a[i][k] * inv_pivot;
                                     inv[i][k] = inv[i][k] * inv_pivot;
@@ -90,12 +271,13 @@ impl GF128Matrix {
                                             }
                                  }
             }
+        if singular != 0 {return None}
         Some(GF128Matrix {  data: inv,  true_size: n})
     }
 
     // Dedicated inversion of Vandermonde Matrix using Sherman-Morrison-Woodbury approach
     #[inline(always)]
-    pub fn invert_vandermonde(&self, sub_size: usize) -> Self {
+    pub fn invert_vandermonde(&self, sub_size: usize) -> Result<Self, RsbeError> {
         let n = sub_size;
         let mut inv_data: SmallVec<[SmallVec<[GF128; MAX_VECTOR_ELEMENTS]>; MAX_VECTOR_ELEMENTS]> = SmallVec::new();        
         // Initialize with zeros
@@ -104,6 +286,7 @@ impl GF128Matrix {
                                         inv_data.push(row);
                                     }        
         inv_data[0][0] = GF128::from(1); // Base case: 1x1 matrix [1] has inverse [1]        
+        let mut singular = 0u128;       // Zero Schur complements, checked after the loop
         for k in 1..n { // Current matrix is (k+1)x(k+1), previous was kxk
                                 // We're adding row k and column k
                                 let mut ainv_u:SmallVec<[GF128; MAX_VECTOR_ELEMENTS]> = SmallVec::new();
@@ -122,7 +305,7 @@ impl GF128Matrix {
             let mut vt_ainv_u = GF128::from(0);
             for i in 0..k { vt_ainv_u += vt_ainv[i] * self.data[i][k]}            
             let schur_complement = self.data[k][k] + vt_ainv_u; // GF: subtraction = addition
-            if schur_complement.is_zero() { panic!("Matrix is not invertible"); }            
+            singular |= schur_complement.zero_mask();
             let gamma = schur_complement.invert();            
             // Update the inverse matrix using block inversion formula
             // Top-left block: A^{-1} + γ * (A^{-1} * u) * (v^T * A^{-1})
@@ -138,13 +321,14 @@ impl GF128Matrix {
             // Bottom-right block: γ
             inv_data[k][k] = gamma;
         }        
-        Self {  data: inv_data, true_size: sub_size}
+        if singular != 0 {return Err(RsbeError::MatrixNotInvertible)}
+        Ok(Self {  data: inv_data, true_size: sub_size})
     }
 
     // Proposed inversion of both Vandermonde Matrices of (kxk) and((k-1)x(k-1)) in a single
     // loop using Sherman-Morrison-Woodbury approach
     #[inline(always)]
-    pub fn invert_vandermonde_both(&self, sub_size: usize) -> (Self, Self) {
+    pub fn invert_vandermonde_both(&self, sub_size: usize) -> Result<(Self, Self), RsbeError> {
         let n = sub_size;
         let mut inv_data: SmallVec<[SmallVec<[GF128; MAX_VECTOR_ELEMENTS]>; MAX_VECTOR_ELEMENTS]> = SmallVec::new();
         let mut sub_inv_data: SmallVec<[SmallVec<[GF128; MAX_VECTOR_ELEMENTS]>; MAX_VECTOR_ELEMENTS]> = SmallVec::new();        
@@ -158,6 +342,7 @@ impl GF128Matrix {
                                         sub_inv_data.push(sub_row);
                                     }        
         inv_data[0][0] = GF128::from(1); // Base case: 1x1 matrix [1] has inverse [1]        
+        let mut singular = 0u128;       // Zero Schur complements, checked after the loop
         for k in 1..n {
             // Current matrix is (k+1)x(k+1), previous was kxk
             // We're adding row k and column k
@@ -190,7 +375,7 @@ impl GF128Matrix {
                 vt_ainv_u += vt_ainv[i] * self.data[i][k];
             }            
             let schur_complement = self.data[k][k] + vt_ainv_u; // GF: subtraction = addition
-            if schur_complement.is_zero() {panic!("Matrix is not invertible");}            
+            singular |= schur_complement.zero_mask();
             let gamma = schur_complement.invert();            
             // Update the inverse matrix using block inversion formula
             // Top-left block: A^{-1} + γ * (A^{-1} * u) * (v^T * A^{-1})
@@ -206,9 +391,17 @@ impl GF128Matrix {
             // Bottom-right block: γ
             inv_data[k][k] = gamma;
         }        
+        if singular != 0 {return Err(RsbeError::MatrixNotInvertible)}
         let full_inverse = Self {   data: inv_data,true_size: sub_size};        
         let sub_inverse = Self {data: sub_inv_data,true_size: sub_size - 1};        
-        (full_inverse, sub_inverse)
+        Ok((full_inverse, sub_inverse))
+    }
+
+    // Inverses of all the leading sub-matrices up to (max_size x max_size), see VandermondeInverses
+    pub fn invert_vandermonde_prefixes(&self, max_size: usize) -> Result<VandermondeInverses, RsbeError> {
+        let mut inverses = VandermondeInverses::default();
+        inverses.extend(self, max_size)?;
+        Ok(inverses)
     }
 
     pub fn matrices_equal(&self, b: &GF128Matrix) -> bool {
@@ -359,12 +552,13 @@ impl From<Vec<Vec<u128>>> for GF128Matrix {
     }
 }
 
-impl From<&[&[&str]]> for GF128Matrix {
-    fn from(hex_strings: &[&[&str]]) -> Self {
+impl TryFrom<&[&[&str]]> for GF128Matrix {
+    type Error = RsbeError;
+    fn try_from(hex_strings: &[&[&str]]) -> Result<Self, Self::Error> {
         let true_size = hex_strings.len();
         for row in hex_strings.iter() {
             if row.len() != true_size {
-                panic!("All rows must have the same number of columns.");
+                return Err(RsbeError::InconsistentDimensions);
             }
         }
         
@@ -375,13 +569,13 @@ impl From<&[&[&str]]> for GF128Matrix {
             for j in 0..hex_strings[i].len() {
                 let hex = hex_strings[i][j];
                 let parsed_u128 = u128::from_str_radix(hex, 16)
-                    .expect(&format!("Failed to parse hexadecimal string: {}", hex));
+                    .map_err(|_| RsbeError::ParseError(hex.to_string()))?;
                 row.push(GF128::from(parsed_u128));
             }
             data.push(row);
         }
         
-        GF128Matrix { data, true_size }
+        Ok(GF128Matrix { data, true_size })
     }
 }
 
@@ -395,13 +589,14 @@ fn parse_str_to_gf128(s: &str) -> Result<GF128, &'static str> {
     Err("Invalid string format")
 }
 
-impl From<&Vec<Vec<&str>>> for GF128Matrix {
-    fn from(hex_or_decimal_strings: &Vec<Vec<&str>>) -> Self {
+impl TryFrom<&Vec<Vec<&str>>> for GF128Matrix {
+    type Error = RsbeError;
+    fn try_from(hex_or_decimal_strings: &Vec<Vec<&str>>) -> Result<Self, Self::Error> {
         let true_size = hex_or_decimal_strings.len();
         
         // Ensure all rows are the same length
         if !hex_or_decimal_strings.iter().all(|row| row.len() == true_size) {
-            panic!("Inconsistent row lengths in input.");
+            return Err(RsbeError::InconsistentDimensions);
         }
         
         let mut data = SmallVec::new();
@@ -414,16 +609,13 @@ impl From<&Vec<Vec<&str>>> for GF128Matrix {
                         row.push(gf_value);
                     }
                     Err(_) => {
-                        panic!(
-                            "Invalid string format in input: {}",
-                            hex_or_decimal_strings[i][j]
-                        );
+                        return Err(RsbeError::ParseError(hex_or_decimal_strings[i][j].to_string()));
                     }
                 }
             }
             data.push(row);
         }
         
-        GF128Matrix { data, true_size }
+        Ok(GF128Matrix { data, true_size })
     }
 }
\ No newline at end of file
@@ -1,5 +1,8 @@
+pub mod backend;
 pub mod field;
 pub mod matrix;
+pub mod poly;
 pub mod vector;
 pub use field::GF128;
-pub use matrix::GF128Matrix;
+pub use matrix::{GF128Matrix, VandermondeInverses, VandermondeSolver};
+pub use poly::GF128Poly;
@@ -0,0 +1,293 @@
+use std::{fmt, ops::{Add, Mul}};
+use zeroize::Zeroize;
+use crate::error::RsbeError;
+use super::{backend, field::GF128};
+
+// Polynomials over GF(2^128), coefficient i being the one of X^i. The coefficients never end with a zero (the zero
+// polynomial has none), so the degree is the number of coefficients minus one. Subtraction is the addition.
+
+// Below this size (of the shortest operand) Karatsuba goes back to the schoolbook product
+pub const KARATSUBA_THRESHOLD: usize = 32;
+// Points evaluated by Horner at the leaves of the subproduct tree of evaluate_many
+const MULTIPOINT_LEAF: usize = 16;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GF128Poly {
+    coefficients: Vec<GF128>,
+}
+
+impl Zeroize for GF128Poly {
+    fn zeroize(&mut self) {
+        self.coefficients.iter_mut().for_each(|c| c.zeroize());
+    }
+}
+
+// Schoolbook product of two non empty coefficient slices : coefficient k is a dot product of a with the reversed b,
+// reduced once
+fn mul_schoolbook(a: &[GF128], b: &[GF128]) -> Vec<GF128> {
+    let (n, m) = (a.len(), b.len());
+    let reversed: Vec<GF128> = b.iter().rev().copied().collect();
+    (0..n + m - 1).map(|k| {    let low = k.saturating_sub(m - 1);
+                                let high = k.min(n - 1);
+                                backend::dot(&a[low..=high], &reversed[m - 1 - k + low..])
+                            }).collect()
+}
+
+// a + b of two coefficient slices of any sizes
+fn add_slices(a: &[GF128], b: &[GF128]) -> Vec<GF128> {
+    let (long, short) = if a.len() >= b.len() {(a, b)} else {(b, a)};
+    let mut sum = long.to_vec();
+    for (s, c) in sum.iter_mut().zip(short) {*s += c}
+    sum
+}
+
+// Karatsuba product of two non empty coefficient slices, split at half of the longest one. When the shortest one
+// does not reach past the split, only the longest is split
+fn mul_karatsuba(a: &[GF128], b: &[GF128]) -> Vec<GF128> {
+    if a.len().min(b.len()) < KARATSUBA_THRESHOLD {return mul_schoolbook(a, b)}
+    let half = a.len().max(b.len()) / 2;
+    let mut product = vec![GF128::from(0); a.len() + b.len() - 1];
+    if a.len() <= half || b.len() <= half {
+        let (long, short) = if a.len() >= b.len() {(a, b)} else {(b, a)};
+        for (k, c) in mul_karatsuba(&long[..half], short).into_iter().enumerate() {product[k] += c}
+        for (k, c) in mul_karatsuba(&long[half..], short).into_iter().enumerate() {product[half + k] += c}
+        return product
+    }
+    let (a0, a1) = a.split_at(half);
+    let (b0, b1) = b.split_at(half);
+    let z0 = mul_karatsuba(a0, b0);
+    let z2 = mul_karatsuba(a1, b1);
+    let mut z1 = mul_karatsuba(&add_slices(a0, a1), &add_slices(b0, b1));
+    for (k, c) in z0.iter().enumerate() {z1[k] += c}
+    for (k, c) in z2.iter().enumerate() {z1[k] += c}
+    for (k, c) in z0.into_iter().enumerate() {product[k] += c}
+    for (k, c) in z1.into_iter().enumerate() {product[half + k] += c}
+    for (k, c) in z2.into_iter().enumerate() {product[2 * half + k] += c}
+    product
+}
+
+// Products of the (X - x_i), leaves of MULTIPOINT_LEAF points first, then pairs of nodes up to the root (last level)
+fn subproduct_tree(points: &[GF128]) -> Vec<Vec<GF128Poly>> {
+    let mut levels: Vec<Vec<GF128Poly>> = vec![points.chunks(MULTIPOINT_LEAF).map(GF128Poly::from_roots).collect()];
+    while levels[levels.len() - 1].len() > 1 {
+        let next = levels[levels.len() - 1].chunks(2).map(|pair| if pair.len() == 2 {pair[0].multiply(&pair[1])}
+                                                                  else {pair[0].clone()}).collect();
+        levels.push(next);
+    }
+    levels
+}
+
+impl GF128Poly {
+    // Trailing zero coefficients are dropped
+    pub fn new(coefficients: Vec<GF128>) -> Self {
+        let mut poly = GF128Poly { coefficients };
+        poly.normalize();
+        poly
+    }
+
+    pub fn zero() -> Self {
+        GF128Poly { coefficients: Vec::new() }
+    }
+
+    pub fn constant(c: GF128) -> Self {
+        GF128Poly::new(vec![c])
+    }
+
+    // c * X^degree
+    pub fn monomial(c: GF128, degree: usize) -> Self {
+        let mut coefficients = vec![GF128::from(0); degree + 1];
+        coefficients[degree] = c;
+        GF128Poly::new(coefficients)
+    }
+
+    // Product of the (X - r) over the given roots, monic of degree roots.len()
+    pub fn from_roots(roots: &[GF128]) -> Self {
+        let mut coefficients = vec![GF128::from(0); roots.len() + 1];
+        coefficients[0] = GF128::from(1);
+        for (i, root) in roots.iter().enumerate() {
+            for k in (1..=i + 1).rev() {coefficients[k] = coefficients[k - 1] + coefficients[k] * *root}
+            coefficients[0] = coefficients[0] * *root;
+        }
+        GF128Poly { coefficients }
+    }
+
+    // Random polynomial of the given degree (non zero leading coefficient)
+    pub fn random(degree: usize) -> Self {
+        let mut coefficients: Vec<GF128> = (0..=degree).map(|_| GF128::random()).collect();
+        while coefficients[degree].is_zero() {coefficients[degree] = GF128::random()}
+        GF128Poly { coefficients }
+    }
+
+    fn normalize(&mut self) {
+        while self.coefficients.last().is_some_and(|c| c.is_zero()) {self.coefficients.pop();}
+    }
+
+    pub fn coefficients(&self) -> &[GF128] {
+        &self.coefficients
+    }
+
+    // None for the zero polynomial
+    pub fn degree(&self) -> Option<usize> {
+        self.coefficients.len().checked_sub(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    pub fn leading_coefficient(&self) -> Option<GF128> {
+        self.coefficients.last().copied()
+    }
+
+    // Value at x (Horner)
+    pub fn eval(&self, x: &GF128) -> GF128 {
+        self.coefficients.iter().rev().fold(GF128::from(0), |acc, c| acc * *x + *c)
+    }
+
+    // Values at all the points, by reducing modulo the nodes of the subproduct tree of the points down to leaves of
+    // MULTIPOINT_LEAF points evaluated by Horner
+    pub fn evaluate_many(&self, points: &[GF128]) -> Vec<GF128> {
+        if points.len() <= MULTIPOINT_LEAF {return points.iter().map(|x| self.eval(x)).collect()}
+        let tree = subproduct_tree(points);
+        let mut remainders = vec![self.rem_monic(&tree[tree.len() - 1][0])];
+        for level in tree.iter().rev().skip(1) {
+            remainders = level.iter().enumerate().map(|(i, node)| remainders[i / 2].rem_monic(node)).collect();
+        }
+        points.chunks(MULTIPOINT_LEAF).zip(&remainders)
+              .flat_map(|(chunk, remainder)| chunk.iter().map(move |x| remainder.eval(x))).collect()
+    }
+
+    pub fn addto(&self, rhs: &GF128Poly) -> Self {
+        GF128Poly::new(add_slices(&self.coefficients, &rhs.coefficients))
+    }
+
+    pub fn scale(&self, c: &GF128) -> Self {
+        GF128Poly::new(self.coefficients.iter().map(|x| *x * *c).collect())
+    }
+
+    pub fn multiply_schoolbook(&self, rhs: &GF128Poly) -> Self {
+        if self.is_zero() || rhs.is_zero() {return GF128Poly::zero()}
+        GF128Poly::new(mul_schoolbook(&self.coefficients, &rhs.coefficients))
+    }
+
+    pub fn multiply_karatsuba(&self, rhs: &GF128Poly) -> Self {
+        if self.is_zero() || rhs.is_zero() {return GF128Poly::zero()}
+        GF128Poly::new(mul_karatsuba(&self.coefficients, &rhs.coefficients))
+    }
+
+    // Karatsuba above KARATSUBA_THRESHOLD coefficients, schoolbook below
+    pub fn multiply(&self, rhs: &GF128Poly) -> Self {
+        self.multiply_karatsuba(rhs)
+    }
+
+    // Quotient and remainder (of degree below the one of the divisor) of the division by a non zero polynomial
+    pub fn divrem(&self, divisor: &GF128Poly) -> Result<(Self, Self), RsbeError> {
+        let Some(lead) = divisor.leading_coefficient() else {return Err(RsbeError::DivisionByZero)};
+        let d = divisor.coefficients.len();
+        if self.coefficients.len() < d {return Ok((GF128Poly::zero(), self.clone()))}
+        let inv_lead = lead.invert();
+        let mut remainder = self.coefficients.clone();
+        let mut quotient = vec![GF128::from(0); remainder.len() - d + 1];
+        for k in (0..quotient.len()).rev() {
+            let q = remainder[k + d - 1] * inv_lead;
+            quotient[k] = q;
+            for (r, c) in remainder[k..k + d].iter_mut().zip(&divisor.coefficients) {*r += q * *c}
+        }
+        remainder.truncate(d - 1);
+        Ok((GF128Poly::new(quotient), GF128Poly::new(remainder)))
+    }
+
+    // Remainder of the division by a monic polynomial, without inversion
+    fn rem_monic(&self, divisor: &GF128Poly) -> Self {
+        let d = divisor.coefficients.len();
+        if self.coefficients.len() < d {return self.clone()}
+        let mut remainder = self.coefficients.clone();
+        for k in (0..remainder.len() - d + 1).rev() {
+            let q = remainder[k + d - 1];
+            for (r, c) in remainder[k..k + d].iter_mut().zip(&divisor.coefficients) {*r += q * *c}
+        }
+        remainder.truncate(d - 1);
+        GF128Poly::new(remainder)
+    }
+
+    // Monic greatest common divisor (Euclid), zero when both polynomials are zero
+    pub fn gcd(&self, rhs: &GF128Poly) -> Result<Self, RsbeError> {
+        let (mut a, mut b) = (self.clone(), rhs.clone());
+        while !b.is_zero() {
+            let (_, r) = a.divrem(&b)?;
+            a = b;
+            b = r;
+        }
+        match a.leading_coefficient() {
+            Some(lead) => Ok(a.scale(&lead.invert())),
+            None => Ok(a),
+        }
+    }
+
+    // Formal derivative : in characteristic 2 only the odd powers remain
+    pub fn derivative(&self) -> Self {
+        GF128Poly::new(self.coefficients.iter().enumerate().skip(1)
+                           .map(|(i, c)| if i % 2 == 1 {*c} else {GF128::from(0)}).collect())
+    }
+
+    // Lagrange interpolation : the polynomial of degree below points.len() taking values[i] at points[i], as the sum
+    // of values[i] / M'(x_i) * M(X) / (X - x_i) with M the product of the (X - x_i). The points must be distinct
+    pub fn interpolate(points: &[GF128], values: &[GF128]) -> Result<Self, RsbeError> {
+        if points.len() != values.len() {return Err(RsbeError::InconsistentDimensions)}
+        if points.is_empty() {return Ok(GF128Poly::zero())}
+        let tree = subproduct_tree(points);
+        let m = &tree[tree.len() - 1][0];
+        let mut weights = m.derivative().evaluate_many(points);
+        if weights.iter().any(|w| w.is_zero()) {return Err(RsbeError::DuplicatePoints)}
+        GF128::batch_invert(&mut weights);
+        let n = points.len();
+        let mut coefficients = vec![GF128::from(0); n];
+        for ((x, y), w) in points.iter().zip(values).zip(&weights) {
+            // M(X) / (X - x) by synthetic division, from the highest coefficient
+            let factor = *y * *w;
+            let mut q = GF128::from(0);
+            for k in (0..n).rev() {
+                q = m.coefficients[k + 1] + q * *x;
+                coefficients[k] += factor * q;
+            }
+        }
+        Ok(GF128Poly::new(coefficients))
+    }
+}
+
+impl From<Vec<u128>> for GF128Poly {
+    fn from(coefficients: Vec<u128>) -> Self {
+        GF128Poly::new(coefficients.into_iter().map(GF128::from).collect())
+    }
+}
+
+impl Add for &GF128Poly {
+    type Output = GF128Poly;
+    fn add(self, rhs: &GF128Poly) -> GF128Poly {
+        self.addto(rhs)
+    }
+}
+
+impl Mul for &GF128Poly {
+    type Output = GF128Poly;
+    fn mul(self, rhs: &GF128Poly) -> GF128Poly {
+        self.multiply(rhs)
+    }
+}
+
+impl fmt::Display for GF128Poly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {return write!(f, "0")}
+        let mut first = true;
+        for (i, c) in self.coefficients.iter().enumerate().rev().filter(|(_, c)| !c.is_zero()) {
+            if !first {write!(f, " + ")?}
+            first = false;
+            match i {
+                0 => write!(f, "{:x}", c)?,
+                1 => write!(f, "{:x}*X", c)?,
+                _ => write!(f, "{:x}*X^{}", c, i)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use crate::{common_ciphers::CommonCipher, galois_arithmetic::field::MAX_VECTOR_E
 
 use super:: GF128;
 use smallvec::SmallVec;
+use zeroize::Zeroize;
 
 #[derive( Clone, Debug)]
 pub struct GF128Vector{
@@ -9,15 +10,22 @@ pub struct GF128Vector{
     pub true_size:usize
 }
 
+impl Zeroize for GF128Vector {
+    fn zeroize(&mut self) {
+        self.elements.iter_mut().for_each(|e| e.zeroize());
+    }
+}
+
 impl GF128Vector {
 
+    // Elements are allocated for at least the inline capacity, and for all of true_size beyond it
     pub fn new(true_size:usize)-> Self{
-        let elements = core::array::from_fn(|_| GF128::from(0)).into();
+        let elements = SmallVec::from_elem(GF128::from(0), true_size.max(MAX_VECTOR_ELEMENTS));
         GF128Vector { elements , true_size}
     }
 
     pub fn random(true_size: usize) -> Self {
-        let elements: SmallVec::<[GF128; MAX_VECTOR_ELEMENTS]> = core::array::from_fn(|_| GF128::random()).into();
+        let elements: SmallVec::<[GF128; MAX_VECTOR_ELEMENTS]> = (0..true_size.max(MAX_VECTOR_ELEMENTS)).map(|_| GF128::random()).collect();
         GF128Vector { elements, true_size }
     }
 
@@ -33,9 +41,7 @@ impl GF128Vector {
     pub fn vec_from_iv(key: &GF128, initial_iv: &GF128, threshold: usize,prp:&CommonCipher) -> Self {
         let elements: SmallVec::<[GF128; MAX_VECTOR_ELEMENTS]> = (0..2*threshold-1)
                 .map(|j| Self::derive_iv(key, initial_iv, j as u64,prp))
-                .collect::<Vec<_>>()
-                .try_into()
-                .unwrap();
+                .collect();
             GF128Vector { elements, true_size: 2*threshold-1 }
         }
 
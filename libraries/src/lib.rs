@@ -1,5 +1,7 @@
 pub mod galois_arithmetic;
 pub use galois_arithmetic::GF128;
+pub mod error;
+pub use error::RsbeError;
 pub mod cipher;
 pub mod common_ciphers;
 pub mod benchmarking;
\ No newline at end of file